@@ -4,6 +4,22 @@ mod m20240926_143036_create_submission_table;
 mod m20241009_142236_create_system_status_table;
 mod m20241010_073350_create_input_objects;
 mod m20241029_154332_create_runstatus_table;
+mod m20241104_091512_add_relative_path_to_file_objects;
+mod m20241106_135248_add_created_by_to_submissions;
+mod m20241111_102934_create_partial_uploads;
+mod m20241113_084417_add_bytes_received_to_file_objects;
+mod m20241118_161205_add_multipart_upload_id_to_file_objects;
+mod m20241121_093027_create_run_metrics;
+mod m20241125_140512_add_is_pinned_to_submissions;
+mod m20241127_101544_create_storage_usage;
+mod m20241202_091534_add_last_run_id_to_submissions;
+mod m20241204_103217_add_details_to_run_status;
+mod m20241206_142953_create_run_events;
+mod m20241209_110412_create_run_queue;
+mod m20241211_153804_add_retry_policy;
+mod m20241213_094627_create_run_schedules;
+mod m20241216_101204_add_unique_upload_paths;
+mod m20241216_134512_add_errors_to_run_metrics;
 
 pub struct Migrator;
 
@@ -15,6 +31,22 @@ impl MigratorTrait for Migrator {
             Box::new(m20241009_142236_create_system_status_table::Migration),
             Box::new(m20241010_073350_create_input_objects::Migration),
             Box::new(m20241029_154332_create_runstatus_table::Migration),
+            Box::new(m20241104_091512_add_relative_path_to_file_objects::Migration),
+            Box::new(m20241106_135248_add_created_by_to_submissions::Migration),
+            Box::new(m20241111_102934_create_partial_uploads::Migration),
+            Box::new(m20241113_084417_add_bytes_received_to_file_objects::Migration),
+            Box::new(m20241118_161205_add_multipart_upload_id_to_file_objects::Migration),
+            Box::new(m20241121_093027_create_run_metrics::Migration),
+            Box::new(m20241125_140512_add_is_pinned_to_submissions::Migration),
+            Box::new(m20241127_101544_create_storage_usage::Migration),
+            Box::new(m20241202_091534_add_last_run_id_to_submissions::Migration),
+            Box::new(m20241204_103217_add_details_to_run_status::Migration),
+            Box::new(m20241206_142953_create_run_events::Migration),
+            Box::new(m20241209_110412_create_run_queue::Migration),
+            Box::new(m20241211_153804_add_retry_policy::Migration),
+            Box::new(m20241213_094627_create_run_schedules::Migration),
+            Box::new(m20241216_101204_add_unique_upload_paths::Migration),
+            Box::new(m20241216_134512_add_errors_to_run_metrics::Migration),
         ]
     }
 }
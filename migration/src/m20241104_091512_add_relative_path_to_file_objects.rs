@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Add the relative path of the file within the uploaded folder
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .add_column(ColumnDef::new(FileObjects::RelativePath).string().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing uploads were flat, so their relative path is the filename
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(FileObjects::Table)
+                    .value(FileObjects::RelativePath, Expr::col(FileObjects::Filename))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .modify_column(
+                        ColumnDef::new(FileObjects::RelativePath)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_file_obj_relative_path")
+                    .table(FileObjects::Table)
+                    .col(FileObjects::RelativePath)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_file_obj_relative_path")
+                    .table(FileObjects::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .drop_column(FileObjects::RelativePath)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FileObjects {
+    Table,
+    Filename,
+    RelativePath,
+}
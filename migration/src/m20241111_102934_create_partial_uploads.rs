@@ -0,0 +1,134 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Partial uploads of the tus concatenation extension. They are tracked
+        // separately from file objects until the final upload joins them.
+        manager
+            .create_table(
+                Table::create()
+                    .table(PartialUploads::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(PartialUploads::Id).uuid().primary_key())
+                    .col(
+                        ColumnDef::new(PartialUploads::SubmissionId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PartialUploads::FinalObjectId).uuid().null())
+                    .col(
+                        ColumnDef::new(PartialUploads::CreatedOn)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PartialUploads::SizeBytes)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PartialUploads::SizeIsDeferred)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(PartialUploads::BytesReceived)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PartialUploads::AllPartsReceived)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(PartialUploads::LastPartReceived)
+                            .date_time()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_partial_upload_submission_id")
+                            .from_tbl(PartialUploads::Table)
+                            .from_col(PartialUploads::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_partial_upload_final_object_id")
+                            .from_tbl(PartialUploads::Table)
+                            .from_col(PartialUploads::FinalObjectId)
+                            .to_tbl(FileObjects::Table)
+                            .to_col(FileObjects::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_partial_upload_submission_id")
+                    .table(PartialUploads::Table)
+                    .col(PartialUploads::SubmissionId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_partial_upload_final_object_id")
+                    .table(PartialUploads::Table)
+                    .col(PartialUploads::FinalObjectId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PartialUploads::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PartialUploads {
+    Table,
+    Id,
+    SubmissionId,
+    FinalObjectId,
+    CreatedOn,
+    SizeBytes,
+    SizeIsDeferred,
+    BytesReceived,
+    AllPartsReceived,
+    LastPartReceived,
+}
+
+#[derive(DeriveIden)]
+enum FileObjects {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
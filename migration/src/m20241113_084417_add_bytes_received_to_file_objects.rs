@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .add_column(
+                        ColumnDef::new(FileObjects::BytesReceived)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Completed uploads have received all of their bytes
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(FileObjects::Table)
+                    .value(
+                        FileObjects::BytesReceived,
+                        Expr::col(FileObjects::SizeBytes),
+                    )
+                    .and_where(Expr::col(FileObjects::AllPartsReceived).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .drop_column(FileObjects::BytesReceived)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FileObjects {
+    Table,
+    SizeBytes,
+    AllPartsReceived,
+    BytesReceived,
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ID of the S3 multipart upload for uploads made through the API
+        // rather than tusd, cleared once the upload is completed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .add_column(
+                        ColumnDef::new(FileObjects::MultipartUploadId)
+                            .string()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjects::Table)
+                    .drop_column(FileObjects::MultipartUploadId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FileObjects {
+    Table,
+    MultipartUploadId,
+}
@@ -0,0 +1,94 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Summary statistics of a successful run, computed from its outputs
+        manager
+            .create_table(
+                Table::create()
+                    .table(RunMetrics::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(RunMetrics::Id).uuid().primary_key())
+                    .col(ColumnDef::new(RunMetrics::SubmissionId).uuid().not_null())
+                    .col(ColumnDef::new(RunMetrics::RunId).big_integer().not_null())
+                    .col(ColumnDef::new(RunMetrics::Source).string().not_null())
+                    .col(
+                        ColumnDef::new(RunMetrics::ReadCount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RunMetrics::TotalBases)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RunMetrics::N50).big_integer().not_null())
+                    .col(ColumnDef::new(RunMetrics::MeanQscore).double().null())
+                    .col(ColumnDef::new(RunMetrics::BarcodeCounts).json().not_null())
+                    .col(
+                        ColumnDef::new(RunMetrics::TimeAddedUtc)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_run_metrics_submission_id")
+                            .from_tbl(RunMetrics::Table)
+                            .from_col(RunMetrics::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Metrics are computed once per run
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_metrics_submission_id_run_id")
+                    .table(RunMetrics::Table)
+                    .col(RunMetrics::SubmissionId)
+                    .col(RunMetrics::RunId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RunMetrics::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RunMetrics {
+    Table,
+    Id,
+    SubmissionId,
+    RunId,
+    Source,
+    ReadCount,
+    TotalBases,
+    N50,
+    MeanQscore,
+    BarcodeCounts,
+    TimeAddedUtc,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
@@ -0,0 +1,158 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Output sizes from the S3 listing, refreshed by a background job
+        manager
+            .create_table(
+                Table::create()
+                    .table(OutputStorageUsage::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OutputStorageUsage::SubmissionId)
+                            .uuid()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputStorageUsage::OutputBytes)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputStorageUsage::OutputCount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputStorageUsage::ArchivedBytes)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OutputStorageUsage::LastUpdated)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_output_storage_usage_submission_id")
+                            .from_tbl(OutputStorageUsage::Table)
+                            .from_col(OutputStorageUsage::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Objects under the deployment's prefix that match no database row
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrphanedObjects::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(OrphanedObjects::Key).string().primary_key())
+                    .col(
+                        ColumnDef::new(OrphanedObjects::SizeBytes)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(OrphanedObjects::LastModified)
+                            .date_time()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(OrphanedObjects::FoundOn)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Per-submission usage, combining the live input sizes with the cached
+        // output sizes, so it can be filtered and sorted like a table
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE VIEW submission_storage_usage AS
+                SELECT
+                    submissions.id AS submission_id,
+                    submissions.name,
+                    submissions.created_by,
+                    COALESCE(inputs.input_bytes, 0) AS input_bytes,
+                    COALESCE(inputs.input_count, 0) AS input_count,
+                    COALESCE(outputs.output_bytes, 0) AS output_bytes,
+                    COALESCE(outputs.output_count, 0) AS output_count,
+                    COALESCE(outputs.archived_bytes, 0) AS archived_bytes,
+                    COALESCE(inputs.input_bytes, 0)
+                        + COALESCE(outputs.output_bytes, 0)
+                        + COALESCE(outputs.archived_bytes, 0) AS total_bytes,
+                    outputs.last_updated AS outputs_last_updated
+                FROM submissions
+                LEFT JOIN (
+                    SELECT
+                        file_object_associations.submission_id,
+                        SUM(file_objects.size_bytes)::BIGINT AS input_bytes,
+                        COUNT(*) AS input_count
+                    FROM file_object_associations
+                    JOIN file_objects
+                        ON file_objects.id = file_object_associations.input_object_id
+                    GROUP BY file_object_associations.submission_id
+                ) AS inputs ON inputs.submission_id = submissions.id
+                LEFT JOIN output_storage_usage AS outputs
+                    ON outputs.submission_id = submissions.id",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP VIEW IF EXISTS submission_storage_usage")
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OrphanedObjects::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OutputStorageUsage::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum OutputStorageUsage {
+    Table,
+    SubmissionId,
+    OutputBytes,
+    OutputCount,
+    ArchivedBytes,
+    LastUpdated,
+}
+
+#[derive(DeriveIden)]
+enum OrphanedObjects {
+    Table,
+    Key,
+    SizeBytes,
+    LastModified,
+    FoundOn,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
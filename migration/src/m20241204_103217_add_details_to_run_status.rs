@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Details of the run's pod, to explain why it is pending or failed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunStatus::Table)
+                    .add_column(ColumnDef::new(RunStatus::RunId).big_integer().null())
+                    .add_column(ColumnDef::new(RunStatus::Reason).string().null())
+                    .add_column(ColumnDef::new(RunStatus::Message).text().null())
+                    .add_column(ColumnDef::new(RunStatus::ExitCode).integer().null())
+                    .add_column(
+                        ColumnDef::new(RunStatus::RestartCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(ColumnDef::new(RunStatus::NodeName).string().null())
+                    .add_column(ColumnDef::new(RunStatus::GpuType).string().null())
+                    .add_column(ColumnDef::new(RunStatus::PendingReason).text().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Rows are now written by the reconciler, so they must not prevent
+        // deleting their submission
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk_run_status_submission_id")
+                    .table(RunStatus::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_run_status_submission_id")
+                    .from(RunStatus::Table, RunStatus::SubmissionId)
+                    .to(Submissions::Table, Submissions::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk_run_status_submission_id")
+                    .table(RunStatus::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_run_status_submission_id")
+                    .from(RunStatus::Table, RunStatus::SubmissionId)
+                    .to(Submissions::Table, Submissions::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunStatus::Table)
+                    .drop_column(RunStatus::RunId)
+                    .drop_column(RunStatus::Reason)
+                    .drop_column(RunStatus::Message)
+                    .drop_column(RunStatus::ExitCode)
+                    .drop_column(RunStatus::RestartCount)
+                    .drop_column(RunStatus::NodeName)
+                    .drop_column(RunStatus::GpuType)
+                    .drop_column(RunStatus::PendingReason)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RunStatus {
+    Table,
+    SubmissionId,
+    RunId,
+    Reason,
+    Message,
+    ExitCode,
+    RestartCount,
+    NodeName,
+    GpuType,
+    PendingReason,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Kubernetes events of a run, kept after the cluster expires them
+        manager
+            .create_table(
+                Table::create()
+                    .table(RunEvents::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(RunEvents::Id).uuid().primary_key())
+                    .col(ColumnDef::new(RunEvents::SubmissionId).uuid().not_null())
+                    .col(ColumnDef::new(RunEvents::RunId).big_integer().not_null())
+                    .col(ColumnDef::new(RunEvents::EventUid).string().not_null())
+                    .col(ColumnDef::new(RunEvents::InvolvedKind).string().not_null())
+                    .col(ColumnDef::new(RunEvents::InvolvedName).string().not_null())
+                    .col(ColumnDef::new(RunEvents::EventType).string().null())
+                    .col(ColumnDef::new(RunEvents::Reason).string().null())
+                    .col(ColumnDef::new(RunEvents::Message).text().null())
+                    .col(
+                        ColumnDef::new(RunEvents::Count)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .col(ColumnDef::new(RunEvents::FirstSeen).date_time().null())
+                    .col(ColumnDef::new(RunEvents::LastSeen).date_time().null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_run_events_submission_id")
+                            .from_tbl(RunEvents::Table)
+                            .from_col(RunEvents::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Events are updated in place as Kubernetes counts repeats
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_events_event_uid")
+                    .table(RunEvents::Table)
+                    .col(RunEvents::EventUid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_events_submission_id_run_id")
+                    .table(RunEvents::Table)
+                    .col(RunEvents::SubmissionId)
+                    .col(RunEvents::RunId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RunEvents::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RunEvents {
+    Table,
+    Id,
+    SubmissionId,
+    RunId,
+    EventUid,
+    InvolvedKind,
+    InvolvedName,
+    EventType,
+    Reason,
+    Message,
+    Count,
+    FirstSeen,
+    LastSeen,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
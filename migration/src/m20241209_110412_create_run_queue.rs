@@ -0,0 +1,132 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::sea_query::extension::postgres::Type;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(QueueStatus::Enum)
+                    .values([
+                        QueueStatus::Queued,
+                        QueueStatus::Dispatched,
+                        QueueStatus::Cancelled,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Runs waiting for the dispatcher to submit them to the cluster
+        manager
+            .create_table(
+                Table::create()
+                    .table(RunQueue::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(RunQueue::Id).uuid().primary_key())
+                    .col(ColumnDef::new(RunQueue::SubmissionId).uuid().not_null())
+                    .col(ColumnDef::new(RunQueue::RunId).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(RunQueue::Priority)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(ColumnDef::new(RunQueue::RequestedBy).string().not_null())
+                    .col(ColumnDef::new(RunQueue::RequesterUsername).string().null())
+                    .col(
+                        ColumnDef::new(RunQueue::Status)
+                            .custom(QueueStatus::Enum)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RunQueue::Error).text().null())
+                    .col(ColumnDef::new(RunQueue::QueuedOn).date_time().not_null())
+                    .col(ColumnDef::new(RunQueue::DispatchedOn).date_time().null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_run_queue_submission_id")
+                            .from_tbl(RunQueue::Table)
+                            .from_col(RunQueue::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_queue_submission_id_run_id")
+                    .table(RunQueue::Table)
+                    .col(RunQueue::SubmissionId)
+                    .col(RunQueue::RunId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // The dispatcher reads the queue in priority order
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_queue_status_priority_queued_on")
+                    .table(RunQueue::Table)
+                    .col(RunQueue::Status)
+                    .col(RunQueue::Priority)
+                    .col(RunQueue::QueuedOn)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RunQueue::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(Type::drop().name(QueueStatus::Enum).if_exists().to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum QueueStatus {
+    #[iden = "queue_status"]
+    Enum,
+    #[iden = "queued"]
+    Queued,
+    #[iden = "dispatched"]
+    Dispatched,
+    #[iden = "cancelled"]
+    Cancelled,
+}
+
+#[derive(DeriveIden)]
+enum RunQueue {
+    Table,
+    Id,
+    SubmissionId,
+    RunId,
+    Priority,
+    RequestedBy, // Keycloak subject, for the per-user limit
+    RequesterUsername,
+    Status,
+    Error, // Why the last dispatch attempt failed
+    QueuedOn,
+    DispatchedOn,
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
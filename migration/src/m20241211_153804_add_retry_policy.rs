@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Retry policy of the submission's runs, one attempt means no retries
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .add_column(
+                        ColumnDef::new(Submissions::RetryMaxAttempts)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .add_column(
+                        ColumnDef::new(Submissions::RetryBackoffSeconds)
+                            .integer()
+                            .not_null()
+                            .default(300),
+                    )
+                    .add_column(ColumnDef::new(Submissions::RetryReasons).json().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Attempts of the same logical run share the run id of the first one
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunQueue::Table)
+                    .add_column(
+                        ColumnDef::new(RunQueue::Attempt)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .add_column(ColumnDef::new(RunQueue::OriginalRunId).big_integer().null())
+                    .add_column(ColumnDef::new(RunQueue::NotBefore).date_time().null())
+                    .add_column(ColumnDef::new(RunQueue::Outcome).string().null())
+                    .add_column(ColumnDef::new(RunQueue::FinishedOn).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunQueue::Table)
+                    .drop_column(RunQueue::Attempt)
+                    .drop_column(RunQueue::OriginalRunId)
+                    .drop_column(RunQueue::NotBefore)
+                    .drop_column(RunQueue::Outcome)
+                    .drop_column(RunQueue::FinishedOn)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Submissions::Table)
+                    .drop_column(Submissions::RetryMaxAttempts)
+                    .drop_column(Submissions::RetryBackoffSeconds)
+                    .drop_column(Submissions::RetryReasons)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    RetryMaxAttempts,
+    RetryBackoffSeconds,
+    RetryReasons,
+}
+
+#[derive(DeriveIden)]
+enum RunQueue {
+    Table,
+    Attempt,
+    OriginalRunId,
+    NotBefore,
+    Outcome,
+    FinishedOn,
+}
@@ -0,0 +1,102 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Runs deferred to a later time, or repeated on a cron schedule,
+        // which the scheduler queues once they are due
+        manager
+            .create_table(
+                Table::create()
+                    .table(RunSchedules::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(RunSchedules::Id).uuid().primary_key())
+                    .col(ColumnDef::new(RunSchedules::SubmissionId).uuid().not_null())
+                    .col(ColumnDef::new(RunSchedules::Schedule).string().null())
+                    .col(
+                        ColumnDef::new(RunSchedules::NextRunOn)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RunSchedules::Priority)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(RunSchedules::RequestedBy)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(RunSchedules::RequesterUsername)
+                            .string()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(RunSchedules::CreatedOn)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(RunSchedules::LastRunOn).date_time().null())
+                    .col(ColumnDef::new(RunSchedules::LastRunId).big_integer().null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_run_schedules_submission_id")
+                            .from_tbl(RunSchedules::Table)
+                            .from_col(RunSchedules::SubmissionId)
+                            .to_tbl(Submissions::Table)
+                            .to_col(Submissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The scheduler looks up the schedules that are due
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_run_schedules_next_run_on")
+                    .table(RunSchedules::Table)
+                    .col(RunSchedules::NextRunOn)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RunSchedules::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RunSchedules {
+    Table,
+    Id,
+    SubmissionId,
+    Schedule,  // Cron expression in UTC, null for a one-off deferred run
+    NextRunOn, // When the run is next queued
+    Priority,
+    RequestedBy, // Keycloak subject, the queued runs count against their limit
+    RequesterUsername,
+    CreatedOn,
+    LastRunOn,
+    LastRunId, // Run id of the last run queued by the schedule
+}
+
+#[derive(DeriveIden)]
+enum Submissions {
+    Table,
+    Id,
+}
@@ -0,0 +1,120 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The path is kept on the association too, so the database rejects a
+        // second upload to the same path of a submission
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjectAssociations::Table)
+                    .add_column(
+                        ColumnDef::new(FileObjectAssociations::RelativePath)
+                            .string()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The first upload to a path keeps it, those that raced it before the
+        // constraint existed are suffixed
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE file_object_associations
+                SET relative_path = CASE
+                    WHEN paths.position = 1 THEN paths.relative_path
+                    ELSE paths.relative_path || '.duplicate-' || paths.iterator
+                END
+                FROM (
+                    SELECT
+                        file_object_associations.iterator,
+                        file_objects.relative_path,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY file_object_associations.submission_id,
+                                file_objects.relative_path
+                            ORDER BY file_objects.created_on
+                        ) AS position
+                    FROM file_object_associations
+                    JOIN file_objects
+                        ON file_objects.id = file_object_associations.input_object_id
+                ) AS paths
+                WHERE paths.iterator = file_object_associations.iterator",
+            )
+            .await?;
+
+        // The file object keeps the same path as its association, which is
+        // what the uploads are listed and given to the workloads by
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE file_objects
+                SET relative_path = file_object_associations.relative_path
+                FROM file_object_associations
+                WHERE file_object_associations.input_object_id = file_objects.id
+                    AND file_object_associations.relative_path <> file_objects.relative_path",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjectAssociations::Table)
+                    .modify_column(
+                        ColumnDef::new(FileObjectAssociations::RelativePath)
+                            .string()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_association_submission_id_relative_path")
+                    .table(FileObjectAssociations::Table)
+                    .unique()
+                    .col(FileObjectAssociations::SubmissionId)
+                    .col(FileObjectAssociations::RelativePath)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_association_submission_id_relative_path")
+                    .table(FileObjectAssociations::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FileObjectAssociations::Table)
+                    .drop_column(FileObjectAssociations::RelativePath)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FileObjectAssociations {
+    Table,
+    SubmissionId,
+    RelativePath,
+}
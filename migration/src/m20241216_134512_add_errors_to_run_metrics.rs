@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // A failed computation is recorded too, so it is retried with a
+        // backoff instead of on every pass
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunMetrics::Table)
+                    .add_column(ColumnDef::new(RunMetrics::Error).text().null())
+                    .add_column(
+                        ColumnDef::new(RunMetrics::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .add_column(ColumnDef::new(RunMetrics::NextAttemptOn).date_time().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RunMetrics::Table)
+                    .drop_column(RunMetrics::Error)
+                    .drop_column(RunMetrics::Attempts)
+                    .drop_column(RunMetrics::NextAttemptOn)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RunMetrics {
+    Table,
+    Error, // Why the last computation failed, null once computed
+    Attempts,
+    NextAttemptOn, // Null once computed or given up on
+}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Deserialize, Default)]
+pub struct ConsistencyCheckOptions {
+    pub fix: Option<bool>, // Repair or delete what is inconsistent
+}
+
+#[derive(ToSchema, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MissingObject,      // Completed upload row without its S3 object
+    SizeMismatch,       // Row and object disagree on the size
+    OrphanedObject,     // Upload object without a row
+    OrphanedOutput,     // Output of a submission that no longer exists
+    UnrecognisedObject, // Key that does not follow any known layout
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct ConsistencyIssue {
+    pub kind: IssueKind,
+    pub key: String,
+    pub object_id: Option<Uuid>,
+    pub db_size_bytes: Option<i64>,
+    pub s3_size_bytes: Option<i64>,
+    pub fixed: bool,
+    pub error: Option<String>, // Set if fixing failed
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct ConsistencyReport {
+    pub fix: bool,
+    pub checked_rows: usize,
+    pub checked_objects: usize,
+    pub issues: Vec<ConsistencyIssue>,
+    pub generated_on: NaiveDateTime,
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct UnrecognisedWorkload {
+    pub name: String,
+    pub reason: String, // Why it could not be attributed to a run
+    pub latest_status: String,
+    pub start_time: Option<DateTime<Utc>>,
+}
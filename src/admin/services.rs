@@ -0,0 +1,272 @@
+use super::models::{ConsistencyIssue, ConsistencyReport, IssueKind, UnrecognisedWorkload};
+use crate::config::Config;
+use crate::external::k8s::models::{PodName, DEPLOYMENT_LABEL};
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::services::{deployment_label, pod_info};
+use crate::storage::services::{classify, ObjectOwner};
+use anyhow::Result;
+use aws_sdk_s3::Client as S3Client;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::Utc;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, ListParams};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QuerySelect, Set};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+async fn delete_s3_object(s3: &S3Client, key: &str) -> Result<()> {
+    let config = Config::from_env();
+    s3.delete_object()
+        .bucket(&config.s3_bucket)
+        .key(key)
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+async fn fix_issue(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    issue: &ConsistencyIssue,
+) -> Result<bool> {
+    match (issue.kind, issue.object_id) {
+        // The upload cannot be recovered, remove the row and its associations
+        (IssueKind::MissingObject, Some(id)) => {
+            crate::uploads::services::delete_object(db, s3, id).await?;
+            Ok(true)
+        }
+        // S3 is the source of truth for the size of a completed upload
+        (IssueKind::SizeMismatch, Some(id)) => {
+            let size_bytes = issue.s3_size_bytes.unwrap_or_default();
+            let obj = crate::uploads::db::ActiveModel {
+                id: Set(id),
+                size_bytes: Set(size_bytes),
+                bytes_received: Set(size_bytes),
+                ..Default::default()
+            };
+            obj.update(db).await?;
+            Ok(true)
+        }
+        // Re-checked right before deleting, the owner may have been created
+        // since the report was put together
+        (IssueKind::OrphanedObject, Some(id)) => {
+            let owned = crate::uploads::db::Entity::find_by_id(id)
+                .one(db)
+                .await?
+                .is_some()
+                || crate::uploads::partials::db::Entity::find_by_id(id)
+                    .one(db)
+                    .await?
+                    .is_some();
+            if owned {
+                return Ok(false);
+            }
+            delete_s3_object(s3, &issue.key).await?;
+            Ok(true)
+        }
+        (IssueKind::OrphanedOutput, Some(id)) => {
+            if crate::submissions::db::Entity::find_by_id(id)
+                .one(db)
+                .await?
+                .is_some()
+            {
+                return Ok(false);
+            }
+            delete_s3_object(s3, &issue.key).await?;
+            Ok(true)
+        }
+        // Unknown keys may belong to something else sharing the prefix
+        _ => Ok(false),
+    }
+}
+
+pub async fn check_consistency(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    fix: bool,
+) -> Result<ConsistencyReport> {
+    // Cross-reference the upload rows and submissions against everything
+    // under the deployment's prefix
+    let config = Config::from_env();
+    let prefix = format!("{}/", config.s3_prefix);
+    let snapshot = Utc::now();
+    let uploads = crate::uploads::db::Entity::find().all(db).await?;
+    let submission_ids: HashSet<Uuid> = crate::submissions::db::Entity::find()
+        .select_only()
+        .column(crate::submissions::db::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    let partial_ids: HashSet<Uuid> = crate::uploads::partials::db::Entity::find()
+        .select_only()
+        .column(crate::uploads::partials::db::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    let upload_ids: HashSet<Uuid> = uploads.iter().map(|upload| upload.id).collect();
+
+    let mut issues: Vec<ConsistencyIssue> = vec![];
+    let issue = |kind, key: &str, object_id, db_size_bytes, s3_size_bytes| ConsistencyIssue {
+        kind,
+        key: key.to_string(),
+        object_id,
+        db_size_bytes,
+        s3_size_bytes,
+        fixed: false,
+        error: None,
+    };
+
+    // Sizes of the upload objects themselves, keyed by upload ID
+    let mut upload_sizes: HashMap<Uuid, i64> = HashMap::new();
+    let mut checked_objects = 0;
+    let mut pages = s3
+        .list_objects_v2()
+        .bucket(&config.s3_bucket)
+        .prefix(&prefix)
+        .into_paginator()
+        .send();
+
+    while let Some(page) = pages.next().await {
+        for object in page?.contents() {
+            checked_objects += 1;
+            let key = object.key().unwrap_or_default();
+            let size_bytes = object.size.unwrap_or(0);
+            // Objects written after the rows were read may belong to uploads
+            // or submissions created during the listing
+            let predates_snapshot = object
+                .last_modified
+                .and_then(|time| time.to_chrono_utc().ok())
+                .is_some_and(|time| time < snapshot);
+            match classify(key, &prefix) {
+                ObjectOwner::Output(id) | ObjectOwner::Archived(id) => {
+                    if predates_snapshot && !submission_ids.contains(&id) {
+                        issues.push(issue(
+                            IssueKind::OrphanedOutput,
+                            key,
+                            Some(id),
+                            None,
+                            Some(size_bytes),
+                        ));
+                    }
+                }
+                ObjectOwner::Upload(id) => {
+                    if !upload_ids.contains(&id) && !partial_ids.contains(&id) {
+                        if !predates_snapshot {
+                            continue;
+                        }
+                        issues.push(issue(
+                            IssueKind::OrphanedObject,
+                            key,
+                            Some(id),
+                            None,
+                            Some(size_bytes),
+                        ));
+                    } else if key == format!("{}{}", prefix, id) {
+                        upload_sizes.insert(id, size_bytes);
+                    }
+                }
+                ObjectOwner::Unknown => issues.push(issue(
+                    IssueKind::UnrecognisedObject,
+                    key,
+                    None,
+                    None,
+                    Some(size_bytes),
+                )),
+            }
+        }
+    }
+
+    // Only completed uploads are expected to have their object in place,
+    // ongoing ones are still being written by tusd or the client
+    for upload in uploads.iter().filter(|upload| upload.all_parts_received) {
+        let key = format!("{}{}", prefix, upload.id);
+        match upload_sizes.get(&upload.id) {
+            None => issues.push(issue(
+                IssueKind::MissingObject,
+                &key,
+                Some(upload.id),
+                Some(upload.size_bytes),
+                None,
+            )),
+            Some(size_bytes) if *size_bytes != upload.size_bytes => issues.push(issue(
+                IssueKind::SizeMismatch,
+                &key,
+                Some(upload.id),
+                Some(upload.size_bytes),
+                Some(*size_bytes),
+            )),
+            _ => {}
+        }
+    }
+
+    if fix {
+        for issue in issues.iter_mut() {
+            match fix_issue(db, s3, issue).await {
+                Ok(fixed) => issue.fixed = fixed,
+                Err(e) => issue.error = Some(e.to_string()),
+            }
+        }
+    }
+
+    Ok(ConsistencyReport {
+        fix,
+        checked_rows: uploads.len(),
+        checked_objects,
+        issues,
+        generated_on: Utc::now().naive_utc(),
+    })
+}
+
+pub async fn get_unrecognised_workloads(
+    kube: &KubeClientProvider,
+) -> Result<Vec<UnrecognisedWorkload>> {
+    // Listed from the cluster rather than the tracker, which only watches
+    // labelled pods, so stray jobs named like ours are found too
+    let config = Config::from_env();
+    let pods: Api<Pod> = Api::namespaced(kube.client().await?, &config.kube_namespace);
+    let deployment = deployment_label();
+
+    Ok(pods
+        .list(&ListParams::default())
+        .await
+        .inspect_err(|e| kube.forget_if_unauthorized(e))?
+        .items
+        .iter()
+        .filter(|pod| {
+            let named_like_ours = pod
+                .metadata
+                .name
+                .as_ref()
+                .is_some_and(|name| name.starts_with(&config.pod_prefix));
+            let labelled_as_ours = pod
+                .metadata
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get(DEPLOYMENT_LABEL))
+                .is_some_and(|value| *value == deployment);
+            named_like_ours || labelled_as_ours
+        })
+        .filter_map(pod_info)
+        .filter_map(|pod_info| {
+            let (name, latest_status, start_time) = (
+                pod_info.name.clone(),
+                pod_info.latest_status.clone(),
+                pod_info.start_time,
+            );
+            PodName::try_from(pod_info)
+                .err()
+                .map(|e| UnrecognisedWorkload {
+                    name,
+                    reason: e.to_string(),
+                    latest_status,
+                    start_time,
+                })
+        })
+        .collect())
+}
@@ -0,0 +1,75 @@
+use crate::common::auth::Role;
+use crate::external::k8s::provider::KubeClientProvider;
+use aws_sdk_s3::Client as S3Client;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing, Extension, Json, Router,
+};
+use axum_keycloak_auth::{
+    instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
+};
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+
+pub fn router(
+    db: DatabaseConnection,
+    keycloak_auth_instance: Arc<KeycloakAuthInstance>,
+    s3: Arc<S3Client>,
+) -> Router {
+    Router::new()
+        .route("/consistency-check", routing::post(check_consistency))
+        .route(
+            "/unrecognised-workloads",
+            routing::get(get_unrecognised_workloads),
+        )
+        .with_state((db, s3))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance)
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .required_roles(vec![Role::Administrator])
+                .build(),
+        )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/consistency-check",
+    responses((status = OK, body = super::models::ConsistencyReport))
+)]
+pub async fn check_consistency(
+    Query(options): Query<super::models::ConsistencyCheckOptions>,
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+) -> Result<Json<super::models::ConsistencyReport>, (StatusCode, Json<String>)> {
+    // Reports only, unless asked to fix
+    let fix = options.fix.unwrap_or(false);
+    match super::services::check_consistency(&db, &s3, fix).await {
+        Ok(report) => Ok(Json(report)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to check consistency".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/unrecognised-workloads",
+    responses((status = OK, body = Vec<super::models::UnrecognisedWorkload>))
+)]
+pub async fn get_unrecognised_workloads(
+    Extension(kube): Extension<Arc<KubeClientProvider>>,
+) -> Result<Json<Vec<super::models::UnrecognisedWorkload>>, (StatusCode, Json<String>)> {
+    // Pods that look like ours but cannot be attributed to a run, so stray
+    // jobs can be cleaned up
+    match super::services::get_unrecognised_workloads(&kube).await {
+        Ok(workloads) => Ok(Json(workloads)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to list workloads".to_string()),
+        )),
+    }
+}
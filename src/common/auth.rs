@@ -1,3 +1,5 @@
+use axum_keycloak_auth::decode::KeycloakToken;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Role {
     Administrator,
@@ -21,3 +23,10 @@ impl From<String> for Role {
         }
     }
 }
+
+pub fn is_admin(token: &KeycloakToken<Role>) -> bool {
+    token
+        .roles
+        .iter()
+        .any(|role| *role.role() == Role::Administrator)
+}
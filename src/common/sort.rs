@@ -8,6 +8,19 @@ pub fn generic_sort<C>(
 where
     C: sea_orm::ColumnTrait,
 {
+    let (sort_column, order_direction) = parse_sort(sort);
+
+    // Find the corresponding column in the logic or use the default column
+    let order_column = order_column_logic
+        .iter()
+        .find(|&&(col_name, _)| col_name == sort_column)
+        .map(|&(_, col)| col)
+        .unwrap_or(db_columns);
+
+    (order_column, order_direction)
+}
+
+pub fn parse_sort(sort: Option<String>) -> (String, Order) {
     // Default sorting values
     let default_sort_column = "id";
     let default_sort_order = "ASC";
@@ -42,12 +55,5 @@ where
         Order::Desc
     };
 
-    // Find the corresponding column in the logic or use the default column
-    let order_column = order_column_logic
-        .iter()
-        .find(|&&(col_name, _)| col_name == sort_column)
-        .map(|&(_, col)| col)
-        .unwrap_or(db_columns);
-
-    (order_column, order_direction)
+    (sort_column, order_direction)
 }
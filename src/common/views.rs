@@ -3,8 +3,10 @@ use crate::common::models::UIConfiguration;
 use crate::external::db;
 use crate::external::db::ServiceName;
 use crate::external::k8s::services::get_pods;
-use axum::{extract::State, http::StatusCode, Json};
+use crate::external::k8s::tracker::PodTracker;
+use axum::{extract::State, http::StatusCode, Extension, Json};
 use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use std::sync::Arc;
 
 #[utoipa::path(
     get,
@@ -18,9 +20,12 @@ use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOr
         )
     )
 )]
-pub async fn healthz(State(db): State<DatabaseConnection>) -> (StatusCode, Json<HealthCheck>) {
+pub async fn healthz(
+    State(db): State<DatabaseConnection>,
+    Extension(tracker): Extension<Arc<PodTracker>>,
+) -> (StatusCode, Json<HealthCheck>) {
     // Get health of the API.
-    match get_pods().await {
+    match get_pods(&tracker) {
         Err(_) => {
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -20,11 +20,26 @@ pub struct Config {
     pub keycloak_url: String,
     pub keycloak_realm: String,
     pub deployment: String,
-    pub _kube_config: PathBuf,
+    pub _kube_config: Option<PathBuf>, // Unset when running in the cluster
     pub kube_namespace: String,
     pub interval_external_services: u64,
     pub submission_base_image: String,
     pub submission_base_image_tag: String,
+    pub tus_hook_secret: String, // Shared secret tusd sends with each hook call
+    pub quota_user_bytes: Option<i64>, // Storage quota per user, unlimited if unset
+    pub quota_project_bytes: Option<i64>, // Storage quota for the whole deployment
+    pub download_token_secret: String, // Signs short-lived tokens, ie. download links and events tickets
+    pub download_token_expiry_hours: u64, // How long a shared download link stays valid
+    pub retention_input_days: Option<i64>, // Delete inputs N days after the outputs were written
+    pub retention_archive_days: Option<i64>, // Move outputs to the archive prefix after N days
+    pub retention_archive_storage_class: Option<String>, // ie. GLACIER, for archived outputs
+    pub retention_output_days: Option<i64>, // Delete outputs N days after written (or archived)
+    pub retention_interval_hours: u64, // How often the retention policy is applied
+    pub storage_usage_interval_minutes: u64, // How often output sizes are re-listed from S3
+    pub max_running_workloads: u64,    // Across all users, further runs wait in the queue
+    pub max_running_workloads_per_user: u64,
+    pub dispatch_interval_seconds: u64, // How often the queue is checked for capacity
+    pub schedule_interval_seconds: u64, // How often scheduled runs are checked for being due
 
     pub s3_prefix: String,  // Prefix within the bucket, ie. labcaller-dev
     pub pod_prefix: String, // What is prefixed to the pod name, ie. labcaller-dev}
@@ -68,9 +83,7 @@ impl Config {
             keycloak_realm: env::var("KEYCLOAK_REALM").expect("KEYCLOAK_REALM must be set"),
             deployment: env::var("DEPLOYMENT")
                 .expect("DEPLOYMENT must be set, this can be local, dev, stage, or prod"),
-            _kube_config: env::var("KUBECONFIG")
-                .expect("KUBECONFIG must be set")
-                .into(),
+            _kube_config: env::var("KUBECONFIG").ok().map(PathBuf::from),
             kube_namespace: env::var("KUBE_NAMESPACE").expect("KUBE_NAMESPACE must be set"),
             interval_external_services: env::var("INTERVAL_EXTERNAL_SERVICES")
                 .unwrap_or_else(|_| "60".to_string())
@@ -80,6 +93,55 @@ impl Config {
                 .expect("SUBMISSION_BASE_IMAGE must be set"),
             submission_base_image_tag: env::var("SUBMISSION_BASE_IMAGE_TAG")
                 .expect("SUBMISSION_BASE_IMAGE_TAG must be set"),
+            tus_hook_secret: env::var("TUS_HOOK_SECRET").expect("TUS_HOOK_SECRET must be set"),
+            quota_user_bytes: env::var("QUOTA_USER_BYTES")
+                .ok()
+                .map(|quota| quota.parse().expect("QUOTA_USER_BYTES must be a number")),
+            quota_project_bytes: env::var("QUOTA_PROJECT_BYTES")
+                .ok()
+                .map(|quota| quota.parse().expect("QUOTA_PROJECT_BYTES must be a number")),
+            download_token_secret: env::var("DOWNLOAD_TOKEN_SECRET")
+                .expect("DOWNLOAD_TOKEN_SECRET must be set"),
+            download_token_expiry_hours: env::var("DOWNLOAD_TOKEN_EXPIRY_HOURS")
+                .unwrap_or_else(|_| "72".to_string())
+                .parse()
+                .unwrap(),
+            retention_input_days: env::var("RETENTION_INPUT_DAYS")
+                .ok()
+                .map(|days| days.parse().expect("RETENTION_INPUT_DAYS must be a number")),
+            retention_archive_days: env::var("RETENTION_ARCHIVE_DAYS").ok().map(|days| {
+                days.parse()
+                    .expect("RETENTION_ARCHIVE_DAYS must be a number")
+            }),
+            retention_archive_storage_class: env::var("RETENTION_ARCHIVE_STORAGE_CLASS").ok(),
+            retention_output_days: env::var("RETENTION_OUTPUT_DAYS").ok().map(|days| {
+                days.parse()
+                    .expect("RETENTION_OUTPUT_DAYS must be a number")
+            }),
+            retention_interval_hours: env::var("RETENTION_INTERVAL_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()
+                .unwrap(),
+            storage_usage_interval_minutes: env::var("STORAGE_USAGE_INTERVAL_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap(),
+            max_running_workloads: env::var("MAX_RUNNING_WORKLOADS")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .unwrap(),
+            max_running_workloads_per_user: env::var("MAX_RUNNING_WORKLOADS_PER_USER")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .unwrap(),
+            dispatch_interval_seconds: env::var("DISPATCH_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .unwrap(),
+            schedule_interval_seconds: env::var("SCHEDULE_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap(),
             db_prefix,
             db_url,
             s3_prefix,
@@ -0,0 +1,92 @@
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::Client as S3Client;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing, Json, Router,
+};
+use jsonwebtoken::errors::ErrorKind;
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+
+pub fn router(s3: Arc<S3Client>) -> Router {
+    // No Keycloak layer, the signed token in the path is the authorisation
+    Router::new()
+        .route("/:token", routing::get(download))
+        .with_state(s3)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/downloads/{token}",
+    responses((status = OK, content_type = "application/octet-stream"))
+)]
+pub async fn download(
+    State(s3): State<Arc<S3Client>>,
+    Path(token): Path<String>,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    // Streams the object through the API rather than redirecting, so the
+    // link also works for clients that cannot reach the S3 domain
+    let claims = match crate::submissions::services::decode_download_token(&token) {
+        Ok(claims) => claims,
+        Err(e) if matches!(e.kind(), ErrorKind::ExpiredSignature) => {
+            return Err((StatusCode::GONE, Json("Download link expired".to_string())))
+        }
+        Err(_) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json("Invalid download link".to_string()),
+            ))
+        }
+    };
+
+    let config = crate::config::Config::from_env();
+    let key = format!(
+        "{}/outputs/{}/{}",
+        config.s3_prefix, claims.submission_id, claims.filename
+    );
+    let object = match s3
+        .get_object()
+        .bucket(&config.s3_bucket)
+        .key(&key)
+        .send()
+        .await
+    {
+        Ok(object) => object,
+        Err(e) if matches!(e.as_service_error(), Some(GetObjectError::NoSuchKey(_))) => {
+            return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string())))
+        }
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to fetch object".to_string()),
+            ))
+        }
+    };
+
+    let filename = claims.filename.split('/').next_back().unwrap_or_default();
+    let content_type = object
+        .content_type
+        .clone()
+        .unwrap_or("application/octet-stream".to_string());
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename.replace('"', "")),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(object.body.into_async_read())),
+    )
+        .into_response();
+    if let Some(content_length) = object.content_length {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, content_length.into());
+    }
+
+    Ok(response)
+}
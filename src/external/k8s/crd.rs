@@ -1,6 +1,7 @@
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(CustomResource, Debug, Serialize, Deserialize, Clone, JsonSchema)]
 #[kube(
@@ -11,11 +12,13 @@ use serde::{Deserialize, Serialize};
 )]
 pub struct TrainingWorkloadSpec {
     pub allow_privilege_escalation: Option<ValueField<bool>>,
+    pub annotations: Option<ItemizedField<String>>, // Propagated to the pods
     pub environment: Environment,
     pub gpu: ValueField<String>, // Using ValueField to match `value` structure
     pub image: ValueField<String>,
     #[serde(rename = "imagePullPolicy")]
     pub image_pull_policy: ValueField<String>,
+    pub labels: Option<ItemizedField<String>>, // Propagated to the pods
     pub name: ValueField<String>,
     pub run_as_gid: Option<ValueField<u32>>,
     pub run_as_uid: Option<ValueField<u32>>,
@@ -32,12 +35,14 @@ pub struct Environment {
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct EnvironmentItems {
     pub input_object_ids: ValueField<String>,
+    pub input_object_paths: ValueField<String>,
     pub s3_access_key: ValueField<String>,
     pub s3_bucket_id: ValueField<String>,
     pub s3_prefix: ValueField<String>,
     pub s3_secret_key: ValueField<String>,
     pub s3_url: ValueField<String>,
     pub submission_id: ValueField<String>,
+    pub run_id: ValueField<String>, // Outputs must be written under outputs/{submission_id}/{run_id}/
     pub base_image: ValueField<String>,
 }
 
@@ -45,3 +50,26 @@ pub struct EnvironmentItems {
 pub struct ValueField<T> {
     pub value: T,
 }
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ItemizedField<T> {
+    pub items: BTreeMap<String, ValueField<T>>,
+}
+
+impl<T: Clone> From<&BTreeMap<String, T>> for ItemizedField<T> {
+    fn from(map: &BTreeMap<String, T>) -> Self {
+        Self {
+            items: map
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        key.clone(),
+                        ValueField {
+                            value: value.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod crd;
 pub mod models;
+pub mod provider;
 pub mod services;
+pub mod tracker;
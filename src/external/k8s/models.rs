@@ -1,53 +1,208 @@
 use crate::config::Config;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+// Labels set on the workloads and their pods, so they can be watched and
+// looked up by selector
+pub const SUBMISSION_ID_LABEL: &str = "labcaller/submission-id";
+pub const RUN_ID_LABEL: &str = "labcaller/run-id";
+pub const DEPLOYMENT_LABEL: &str = "labcaller/deployment";
+pub const REQUESTER_LABEL: &str = "labcaller/requester"; // Keycloak subject
+pub const PRESET_LABEL: &str = "labcaller/preset"; // Tag of the base image
+
+// Annotations for values that are not valid label values
+pub const REQUESTER_USERNAME_ANNOTATION: &str = "labcaller/requester-username";
+pub const IMAGE_ANNOTATION: &str = "labcaller/image";
+
+pub const GPU_PRODUCT_LABEL: &str = "nvidia.com/gpu.product"; // On nodes
+
 #[derive(Debug)]
 pub struct PodInfo {
     pub name: String,
+    pub labels: BTreeMap<String, String>,
     pub start_time: Option<DateTime<Utc>>,
     pub latest_status: String,
     pub latest_status_time: Option<DateTime<Utc>>,
+    pub container: ContainerState,
+    pub node_name: Option<String>,
+}
+
+// State of the pod's main container, or of the first one that is not
+// running or completed normally
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ContainerState {
+    pub reason: Option<String>, // ie. ImagePullBackOff, OOMKilled, Error
+    pub message: Option<String>,
+    pub exit_code: Option<i32>,
+    pub restart_count: i32,
 }
 
 #[derive(Serialize, Debug, Clone)]
 pub struct PodName {
+    pub name: String,
     pub prefix: String,
     pub submission_id: Uuid,
     pub start_time: Option<DateTime<Utc>>,
     pub latest_status: String,
     pub latest_status_time: Option<DateTime<Utc>>,
     pub run_id: u64,
+    pub container: ContainerState,
+    pub node_name: Option<String>,
+    pub requester: Option<String>, // Keycloak subject, if the workload is labelled
 }
 
-impl From<PodInfo> for PodName {
-    fn from(pod_info: PodInfo) -> Self {
-        let config = Config::from_env();
+#[derive(Debug, Clone, PartialEq)]
+pub enum PodNameError {
+    UnexpectedStructure,         // Not <prefix>-<uuid>-<run_id>-x-x
+    InvalidRunId(String),        // The part where the run id is expected
+    InvalidSubmissionId(String), // The part where the UUID is expected
+}
 
-        // Strip the prefix from the pod name, regardless of hyphens
-        let name_without_prefix = pod_info
-            .name
-            .strip_prefix(&format!("{}-", config.pod_prefix))
-            .unwrap_or(&pod_info.name); // fallback if prefix is absent
+impl std::fmt::Display for PodNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PodNameError::UnexpectedStructure => {
+                write!(f, "Pod name does not have the expected structure")
+            }
+            PodNameError::InvalidRunId(part) => write!(f, "Invalid run ID format: {}", part),
+            PodNameError::InvalidSubmissionId(part) => write!(f, "Invalid UUID format: {}", part),
+        }
+    }
+}
 
-        // Reverse split to isolate <UUID>-<run_id>-x-x parts
-        let parts: Vec<&str> = name_without_prefix.rsplitn(4, '-').collect();
+impl std::error::Error for PodNameError {}
 
-        if parts.len() < 4 {
-            panic!("Pod name does not have the expected structure");
-        }
+impl TryFrom<PodInfo> for PodName {
+    type Error = PodNameError;
+
+    fn try_from(pod_info: PodInfo) -> Result<Self, Self::Error> {
+        PodName::parse(pod_info, &Config::from_env().pod_prefix)
+    }
+}
+
+impl PodName {
+    fn parse(pod_info: PodInfo, pod_prefix: &str) -> Result<Self, PodNameError> {
+        // Workloads are labelled with their ids, the name is only parsed for
+        // workloads submitted before they were
+        let labelled_ids = pod_info
+            .labels
+            .get(SUBMISSION_ID_LABEL)
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .zip(
+                pod_info
+                    .labels
+                    .get(RUN_ID_LABEL)
+                    .and_then(|run_id| run_id.parse().ok()),
+            );
+        let (submission_id, run_id) = match labelled_ids {
+            Some(ids) => ids,
+            None => {
+                // Strip the prefix from the pod name, regardless of hyphens
+                let name_without_prefix = pod_info
+                    .name
+                    .strip_prefix(&format!("{}-", pod_prefix))
+                    .unwrap_or(&pod_info.name); // fallback if prefix is absent
 
-        let run_id: u64 = parts[2].parse().expect("Invalid run ID format");
-        let submission_id = Uuid::parse_str(parts[3]).expect("Invalid UUID format");
+                // Reverse split to isolate <UUID>-<run_id>-x-x parts
+                let parts: Vec<&str> = name_without_prefix.rsplitn(4, '-').collect();
+                if parts.len() < 4 {
+                    return Err(PodNameError::UnexpectedStructure);
+                }
 
-        PodName {
-            prefix: config.pod_prefix.clone(),
+                let run_id: u64 = parts[2]
+                    .parse()
+                    .map_err(|_| PodNameError::InvalidRunId(parts[2].to_string()))?;
+                let submission_id = Uuid::parse_str(parts[3])
+                    .map_err(|_| PodNameError::InvalidSubmissionId(parts[3].to_string()))?;
+                (submission_id, run_id)
+            }
+        };
+
+        Ok(PodName {
+            requester: pod_info.labels.get(REQUESTER_LABEL).cloned(),
+            name: pod_info.name,
+            prefix: pod_prefix.to_string(),
             submission_id,
             start_time: pod_info.start_time,
             latest_status: pod_info.latest_status,
             latest_status_time: pod_info.latest_status_time,
             run_id,
+            container: pod_info.container,
+            node_name: pod_info.node_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIX: &str = "labcaller-dev";
+    const SUBMISSION_ID: &str = "3f1a2b4c-5d6e-4f70-8192-a3b4c5d6e7f8";
+
+    fn pod_info(name: &str, labels: &[(&str, &str)]) -> PodInfo {
+        PodInfo {
+            name: name.to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            start_time: None,
+            latest_status: "Running".to_string(),
+            latest_status_time: None,
+            container: ContainerState::default(),
+            node_name: None,
         }
     }
+
+    #[test]
+    fn parses_ids_from_the_name() {
+        let name = format!("{}-{}-2-abcde-fghij", PREFIX, SUBMISSION_ID);
+        let pod = PodName::parse(pod_info(&name, &[]), PREFIX).unwrap();
+
+        assert_eq!(pod.submission_id, Uuid::parse_str(SUBMISSION_ID).unwrap());
+        assert_eq!(pod.run_id, 2);
+        assert_eq!(pod.prefix, PREFIX);
+        assert_eq!(pod.requester, None);
+    }
+
+    #[test]
+    fn prefers_labels_over_the_name() {
+        let pod = PodName::parse(
+            pod_info(
+                "renamed-pod",
+                &[
+                    (SUBMISSION_ID_LABEL, SUBMISSION_ID),
+                    (RUN_ID_LABEL, "7"),
+                    (REQUESTER_LABEL, "subject"),
+                ],
+            ),
+            PREFIX,
+        )
+        .unwrap();
+
+        assert_eq!(pod.submission_id, Uuid::parse_str(SUBMISSION_ID).unwrap());
+        assert_eq!(pod.run_id, 7);
+        assert_eq!(pod.requester.as_deref(), Some("subject"));
+    }
+
+    #[test]
+    fn rejects_unexpected_names() {
+        let parse = |name: &str| PodName::parse(pod_info(name, &[]), PREFIX).unwrap_err();
+
+        assert_eq!(
+            parse("labcaller-dev-pod"),
+            PodNameError::UnexpectedStructure
+        );
+        assert_eq!(
+            parse(&format!("{}-{}-x-abcde-fghij", PREFIX, SUBMISSION_ID)),
+            PodNameError::InvalidRunId("x".to_string())
+        );
+        assert_eq!(
+            parse("labcaller-dev-not-a-uuid-2-abcde-fghij"),
+            PodNameError::InvalidSubmissionId("not-a-uuid".to_string())
+        );
+    }
 }
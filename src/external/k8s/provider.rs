@@ -0,0 +1,304 @@
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, DecodingKey, Validation};
+use kube::{
+    config::{AuthInfo, Kubeconfig},
+    Client, Config as KubeConfig,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Mutex;
+
+// Refresh the id token this long before it expires, so requests in flight
+// are not made with a token about to be rejected
+const REFRESH_MARGIN_SECONDS: i64 = 60;
+// How long to keep a client whose id token has no readable expiry
+const UNKNOWN_EXPIRY_SECONDS: i64 = 300;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    refresh_token: Option<String>, // Set if the identity provider rotates it
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    exp: i64,
+}
+
+struct CachedClient {
+    client: Client,
+    expires_at: Option<DateTime<Utc>>, // None if kube refreshes credentials itself
+}
+
+#[derive(Default)]
+pub struct KubeClientProvider {
+    cached: Mutex<Option<CachedClient>>,
+    refresh_token: Mutex<Option<String>>, // Latest refresh token, once rotated
+    rejected: AtomicBool,                 // Set once the API server rejects the cached client
+}
+
+#[derive(Deserialize)]
+struct OidcDiscovery {
+    token_endpoint: String,
+}
+
+async fn refresh_oidc_token(
+    provider_config: &HashMap<String, String>,
+    refresh_token: &str,
+) -> Result<TokenResponse> {
+    let setting = |key: &str| {
+        provider_config
+            .get(key)
+            .ok_or_else(|| anyhow!("OIDC auth provider in kubeconfig is missing '{}'", key))
+    };
+    let idp_issuer_url = setting("idp-issuer-url")?;
+    let client_id = setting("client-id")?;
+    let client = reqwest::Client::new();
+
+    // Look up the token endpoint rather than assuming the issuer's URL layout
+    let discovery_url = format!(
+        "{}/.well-known/openid-configuration",
+        idp_issuer_url.trim_end_matches('/')
+    );
+    let discovery: OidcDiscovery = match client.get(&discovery_url).send().await {
+        Ok(res) if res.status().is_success() => res.json().await?,
+        Ok(res) => {
+            return Err(anyhow!(
+                "Failed to discover OIDC token endpoint at {}: {}",
+                discovery_url,
+                res.status()
+            ))
+        }
+        Err(e) => {
+            return Err(anyhow!(
+                "Failed to discover OIDC token endpoint at {}: {}",
+                discovery_url,
+                e
+            ))
+        }
+    };
+
+    let mut params = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id.as_str()),
+    ];
+    if let Some(client_secret) = provider_config.get("client-secret") {
+        params.push(("client_secret", client_secret.as_str()));
+    }
+    let res = match client
+        .post(&discovery.token_endpoint)
+        .form(&params)
+        .send()
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => {
+            return Err(anyhow!("Failed to refresh token: {}", e));
+        }
+    };
+
+    if res.status().is_success() {
+        Ok(res.json().await?)
+    } else {
+        let error_text = res.text().await?;
+        Err(anyhow!("Failed to refresh token: {}", error_text))
+    }
+}
+
+fn current_auth_info_name(kubeconfig: &Kubeconfig) -> Result<String> {
+    // Resolve the user of the current context, the same way kubectl does
+    let current_context_name = kubeconfig
+        .current_context
+        .as_ref()
+        .ok_or_else(|| anyhow!("No current context set in kubeconfig"))?;
+    let context = kubeconfig
+        .contexts
+        .iter()
+        .find(|ctx| &ctx.name == current_context_name)
+        .ok_or_else(|| {
+            anyhow!(
+                "Current context '{}' not found in kubeconfig",
+                current_context_name
+            )
+        })?
+        .context
+        .as_ref()
+        .ok_or_else(|| {
+            anyhow!(
+                "Context '{}' in kubeconfig has no details",
+                current_context_name
+            )
+        })?;
+
+    Ok(context.user.clone())
+}
+
+fn current_auth_info<'a>(kubeconfig: &'a mut Kubeconfig, name: &str) -> Result<&'a mut AuthInfo> {
+    kubeconfig
+        .auth_infos
+        .iter_mut()
+        .find(|ai| ai.name == name)
+        .ok_or_else(|| anyhow!("User '{}' not found in kubeconfig", name))?
+        .auth_info
+        .as_mut()
+        .ok_or_else(|| anyhow!("User '{}' in kubeconfig has no details", name))
+}
+
+fn token_expiry(id_token: &str) -> Option<DateTime<Utc>> {
+    // Only the expiry is needed, the API server verifies the token itself
+    let mut validation = Validation::default();
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    let data = decode::<TokenClaims>(id_token, &DecodingKey::from_secret(&[]), &validation).ok()?;
+    DateTime::from_timestamp(data.claims.exp, 0)
+}
+
+fn persist_refresh_token(
+    kubeconfig_path: &Path,
+    auth_info_name: &str,
+    refresh_token: &str,
+) -> Result<()> {
+    // Write a rotated refresh token back to the kubeconfig, so a restart does
+    // not reuse the previous one, which the identity provider has revoked.
+    // The file is parsed as is, so relative paths in it are kept unchanged
+    let yaml_str = std::fs::read_to_string(kubeconfig_path)?;
+    let mut kubeconfig: Kubeconfig = serde_yaml::from_str(&yaml_str)?;
+    if let Some(auth_provider) = current_auth_info(&mut kubeconfig, auth_info_name)?
+        .auth_provider
+        .as_mut()
+    {
+        auth_provider
+            .config
+            .insert("refresh-token".to_string(), refresh_token.to_string());
+    }
+    std::fs::write(kubeconfig_path, serde_yaml::to_string(&kubeconfig)?)?;
+
+    Ok(())
+}
+
+impl KubeClientProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn forget_if_unauthorized(&self, err: &kube::Error) {
+        // A revoked or expired token is only noticed by the API server, the
+        // next call then builds a fresh client instead of reusing it
+        if matches!(err, kube::Error::Api(response) if response.code == 401) {
+            self.rejected.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub async fn client(&self) -> Result<Client> {
+        // Reuse the cached client until its token is about to expire
+        let mut cached = self.cached.lock().await;
+        if self.rejected.swap(false, Ordering::SeqCst) {
+            *cached = None;
+        }
+        if let Some(cached) = cached.as_ref() {
+            let still_valid = cached.expires_at.is_none_or(|expires_at| {
+                expires_at - Duration::seconds(REFRESH_MARGIN_SECONDS) > Utc::now()
+            });
+            if still_valid {
+                return Ok(cached.client.clone());
+            }
+        }
+
+        let app_config = Config::from_env();
+        let fresh = match &app_config._kube_config {
+            Some(kubeconfig_path) => self.client_from_kubeconfig(kubeconfig_path).await?,
+            // Without a kubeconfig, use the pod's service account
+            None => {
+                let config = KubeConfig::incluster().map_err(|e| {
+                    anyhow!(
+                        "KUBECONFIG is not set and in-cluster config is unavailable: {}",
+                        e
+                    )
+                })?;
+                CachedClient {
+                    client: Client::try_from(config)?,
+                    expires_at: None,
+                }
+            }
+        };
+        let client = fresh.client.clone();
+        *cached = Some(fresh);
+
+        Ok(client)
+    }
+
+    async fn client_from_kubeconfig(&self, kubeconfig_path: &Path) -> Result<CachedClient> {
+        // Read the kubeconfig, resolving certificate paths relative to it
+        let mut kubeconfig = Kubeconfig::read_from(kubeconfig_path).map_err(|e| {
+            anyhow!(
+                "Failed to read kubeconfig {}: {}",
+                kubeconfig_path.display(),
+                e
+            )
+        })?;
+        let auth_info_name = current_auth_info_name(&kubeconfig)?;
+        let auth_info = current_auth_info(&mut kubeconfig, &auth_info_name)?;
+
+        // Exec plugins, bearer tokens (static or from a file) and client
+        // certificates are handled by kube itself, which reruns exec plugins
+        // when their credentials expire. Only the OIDC auth provider is
+        // refreshed here
+        let mut expires_at = None;
+        if let Some(auth_provider) = auth_info.auth_provider.take() {
+            if auth_provider.name != "oidc" {
+                return Err(anyhow!(
+                    "Unsupported auth provider '{}' for user '{}' in kubeconfig",
+                    auth_provider.name,
+                    auth_info_name
+                ));
+            }
+
+            // Prefer a refresh token rotated since the kubeconfig was written
+            let mut latest_refresh_token = self.refresh_token.lock().await;
+            let refresh_token = latest_refresh_token
+                .clone()
+                .or_else(|| auth_provider.config.get("refresh-token").cloned())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "OIDC auth provider for user '{}' has no refresh token",
+                        auth_info_name
+                    )
+                })?;
+
+            let tokens = refresh_oidc_token(&auth_provider.config, &refresh_token).await?;
+            if let Some(rotated) = tokens
+                .refresh_token
+                .filter(|rotated| *rotated != refresh_token)
+            {
+                if let Err(e) = persist_refresh_token(kubeconfig_path, &auth_info_name, &rotated) {
+                    println!("Failed to persist rotated refresh token: {}", e);
+                }
+                *latest_refresh_token = Some(rotated);
+            }
+            expires_at = Some(
+                token_expiry(&tokens.id_token)
+                    .unwrap_or_else(|| Utc::now() + Duration::seconds(UNKNOWN_EXPIRY_SECONDS)),
+            );
+
+            // Replace the auth provider with the fresh id token
+            auth_info.token = Some(Secret::new(tokens.id_token));
+        }
+
+        // Build the Kubernetes client with the updated kubeconfig
+        let config = KubeConfig::from_custom_kubeconfig(kubeconfig, &Default::default())
+            .await
+            .map_err(|e| anyhow!("Invalid kubeconfig {}: {}", kubeconfig_path.display(), e))?;
+
+        Ok(CachedClient {
+            client: Client::try_from(config)?,
+            expires_at,
+        })
+    }
+}
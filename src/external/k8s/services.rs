@@ -1,213 +1,308 @@
-use super::models::PodName;
+use super::crd::TrainingWorkload;
+use super::models::{
+    ContainerState, PodInfo, PodName, DEPLOYMENT_LABEL, GPU_PRODUCT_LABEL, IMAGE_ANNOTATION,
+    PRESET_LABEL, REQUESTER_LABEL, REQUESTER_USERNAME_ANNOTATION, RUN_ID_LABEL,
+    SUBMISSION_ID_LABEL,
+};
+use super::provider::KubeClientProvider;
+use super::tracker::PodTracker;
 use crate::config::Config;
-use anyhow::{anyhow, Error, Result};
+use anyhow::{Error, Result};
 use chrono::{DateTime, Utc};
-use k8s_openapi::api::core::v1::Pod;
-use kube::{
-    api::{Api, ListParams},
-    config::Kubeconfig,
-    Client, Config as KubeConfig,
-};
-use secrecy::Secret;
-use serde::Deserialize;
-use std::fs::File;
-use std::io::Read;
+use k8s_openapi::api::core::v1::{ContainerStatus, Event, Node, Pod};
+use kube::api::{Api, DeleteParams, ListParams};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Deserialize)]
-struct TokenResponse {
-    id_token: String,
-}
-
-async fn refresh_oidc_token(refresh_token: &str, idp_issuer_url: &str) -> Result<String> {
-    let client = reqwest::Client::new();
-    let params = [
-        ("grant_type", "refresh_token"),
-        ("refresh_token", refresh_token),
-        ("client_id", "runai-cli"),
-    ];
-    let url = format!("{}/protocol/openid-connect/token", idp_issuer_url);
-    let res = match client.post(&url).form(&params).send().await {
-        Ok(res) => res,
-        Err(e) => {
-            return Err(anyhow!("Failed to refresh token: {}", e));
-        }
-    };
+fn label_value(value: &str) -> String {
+    // Label values are at most 63 alphanumerics, '-', '_' or '.', and must
+    // start and end with an alphanumeric
+    let value: String = value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '-',
+        })
+        .take(63)
+        .collect();
 
-    if res.status().is_success() {
-        let token_response: TokenResponse = res.json().await?;
-        Ok(token_response.id_token)
-    } else {
-        let error_text = res.text().await?;
-        Err(anyhow!("Failed to refresh token: {}", error_text))
-    }
+    value
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
 }
 
-fn extract_refresh_token(kubeconfig: &Kubeconfig) -> Option<String> {
-    for named_auth_info in &kubeconfig.auth_infos {
-        if let Some(auth_info) = &named_auth_info.auth_info {
-            if let Some(auth_provider) = &auth_info.auth_provider {
-                if auth_provider.name == "oidc" {
-                    // Directly access the config HashMap
-                    if let Some(refresh_token) = auth_provider.config.get("refresh-token") {
-                        return Some(refresh_token.clone());
-                    }
-                }
-            }
-        }
-    }
-    None
+pub fn workload_labels(
+    submission_id: Uuid,
+    run_id: i64,
+    requester: &str,
+    preset: &str,
+) -> BTreeMap<String, String> {
+    // Labels the pods are tracked and selected by, see PodTracker
+    BTreeMap::from([
+        (SUBMISSION_ID_LABEL.to_string(), submission_id.to_string()),
+        (RUN_ID_LABEL.to_string(), run_id.to_string()),
+        (DEPLOYMENT_LABEL.to_string(), deployment_label()),
+        (REQUESTER_LABEL.to_string(), label_value(requester)),
+        (PRESET_LABEL.to_string(), label_value(preset)),
+    ])
 }
 
-pub async fn get_pods() -> Result<Vec<crate::external::k8s::models::PodName>, Error> {
-    // Get app config and kube client
+pub fn workload_name(submission_id: Uuid, run_id: i64) -> String {
+    // Name of the TrainingWorkload, its pods are named after it
     let app_config = Config::from_env();
-    let client = refresh_token_and_get_client().await?;
+    format!("{}-{}-{}", app_config.pod_prefix, submission_id, run_id)
+}
+
+pub fn deployment_label() -> String {
+    // Identifies the workloads submitted by this deployment of the API
+    let app_config = Config::from_env();
+    label_value(&app_config.pod_prefix)
+}
+
+pub fn deployment_selector() -> String {
+    format!("{}={}", DEPLOYMENT_LABEL, deployment_label())
+}
+
+pub fn workload_annotations(requester_username: &str, image: &str) -> BTreeMap<String, String> {
+    BTreeMap::from([
+        (
+            REQUESTER_USERNAME_ANNOTATION.to_string(),
+            requester_username.to_string(),
+        ),
+        (IMAGE_ANNOTATION.to_string(), image.to_string()),
+    ])
+}
 
-    // Get pods from Kubernetes API
-    let pods: Api<Pod> = Api::namespaced(client, &app_config.kube_namespace);
-    let lp = ListParams::default();
+pub fn pod_info(pod: &Pod) -> Option<PodInfo> {
+    let name = pod.metadata.name.clone()?;
 
-    let pod_list = match pods.list(&lp).await {
-        Ok(pod_list) => pod_list,
-        Err(e) => return Err(e.into()),
+    let start_time: Option<DateTime<Utc>> = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.start_time.clone())
+        .map(|time| time.0);
+
+    let phase = pod.status.as_ref().and_then(|status| status.phase.clone());
+
+    // Get the latest status time by the latest container status.conditions ordered by last_transition_time
+    let latest_status_time: Option<DateTime<Utc>> = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| {
+            conditions
+                .iter()
+                .max_by_key(|condition| condition.last_transition_time.clone())
+        })
+        .and_then(|condition| condition.last_transition_time.clone())
+        .map(|time| time.0);
+
+    Some(PodInfo {
+        name,
+        labels: pod.metadata.labels.clone().unwrap_or_default(),
+        start_time,
+        latest_status: phase.unwrap_or_else(|| "Unknown".to_string()),
+        latest_status_time,
+        container: container_state(pod),
+        node_name: pod.spec.as_ref().and_then(|spec| spec.node_name.clone()),
+    })
+}
+
+fn container_state(pod: &Pod) -> ContainerState {
+    let statuses = pod
+        .status
+        .as_ref()
+        .and_then(|status| status.container_statuses.clone())
+        .unwrap_or_default();
+
+    // Prefer a container that is waiting or has terminated abnormally
+    let abnormal = |status: &&ContainerStatus| {
+        status.state.as_ref().is_some_and(|state| {
+            state.waiting.is_some()
+                || state
+                    .terminated
+                    .as_ref()
+                    .is_some_and(|terminated| terminated.exit_code != 0)
+        })
     };
-    let pod_infos: Vec<crate::external::k8s::models::PodName> = pod_list
-        .clone()
-        .items
-        .into_iter()
-        .filter_map(|pod| {
-            let name = pod.metadata.name.clone()?;
+    let mut container = ContainerState::default();
+    if let Some(status) = statuses.iter().find(abnormal).or(statuses.first()) {
+        container.restart_count = status.restart_count;
+        if let Some(state) = &status.state {
+            if let Some(waiting) = &state.waiting {
+                container.reason = waiting.reason.clone();
+                container.message = waiting.message.clone();
+            } else if let Some(terminated) = &state.terminated {
+                container.reason = terminated.reason.clone();
+                container.message = terminated.message.clone();
+                container.exit_code = Some(terminated.exit_code);
+            }
+        }
 
-            // Add this line to filter by prefix
-            if !name.starts_with(&app_config.pod_prefix) {
-                return None;
+        // A restarted container reports why its previous run ended
+        if container.reason.is_none() {
+            if let Some(terminated) = status
+                .last_state
+                .as_ref()
+                .and_then(|state| state.terminated.as_ref())
+            {
+                container.reason = terminated.reason.clone();
+                container.exit_code = Some(terminated.exit_code);
             }
+        }
+    }
 
-            let start_time: Option<DateTime<Utc>> = match pod.status.clone().unwrap().start_time {
-                Some(time) => Some(time.0),
-                None => None,
-            };
-
-            let phase = pod.status.as_ref().and_then(|status| status.phase.clone());
-
-            // Get the latest status time by the latest container status.conditions ordered by last_transition_time
-            let latest_status_time: Option<DateTime<Utc>> =
-                match pod.status.as_ref().and_then(|status| {
-                    status.conditions.as_ref().and_then(|conditions| {
-                        conditions
-                            .iter()
-                            .max_by_key(|condition| condition.last_transition_time.clone())
-                            .map(|condition| condition.last_transition_time.clone())
-                    })
-                }) {
-                    Some(time) => Some(time.unwrap().0),
-                    None => None,
-                };
-
-            Some(
-                crate::external::k8s::models::PodInfo {
-                    name,
-                    start_time,
-                    latest_status: phase.unwrap_or_else(|| "Unknown".to_string()),
-                    latest_status_time,
-                }
-                .into(),
-            )
-        })
-        .collect();
+    // Evicted and preempted pods are explained at the pod level, and may
+    // have no container statuses at all
+    if container.reason.is_none() {
+        if let Some(status) = &pod.status {
+            container.reason = status.reason.clone();
+            container.message = status.message.clone();
+        }
+    }
 
-    Ok(pod_infos)
+    container
 }
 
-pub async fn refresh_token_and_get_client() -> Result<Client> {
+pub async fn get_pending_reason(
+    kube: &KubeClientProvider,
+    pod_name: &str,
+) -> Result<Option<String>> {
+    // The scheduler and kubelet explain why a pod is pending in its events,
+    // ie. FailedScheduling: 0/4 nodes are available: insufficient nvidia.com/gpu
     let app_config = Config::from_env();
+    let events: Api<Event> = Api::namespaced(kube.client().await?, &app_config.kube_namespace);
+    let lp = ListParams::default().fields(&format!(
+        "involvedObject.kind=Pod,involvedObject.name={},type=Warning",
+        pod_name
+    ));
 
-    // Read and parse the kubeconfig file
-    let mut kubeconfig = {
-        let mut file = File::open(&app_config._kube_config)?;
-        let mut yaml_str = String::new();
-        file.read_to_string(&mut yaml_str)?;
-        serde_yaml::from_str::<Kubeconfig>(&yaml_str)?
-    };
+    Ok(events
+        .list(&lp)
+        .await
+        .inspect_err(|e| kube.forget_if_unauthorized(e))?
+        .items
+        .into_iter()
+        .max_by_key(|event| event.last_timestamp.clone().map(|time| time.0))
+        .map(|event| {
+            format!(
+                "{}: {}",
+                event.reason.unwrap_or_default(),
+                event.message.unwrap_or_default()
+            )
+        }))
+}
 
-    let refresh_token = extract_refresh_token(&kubeconfig)
-        .ok_or_else(|| anyhow!("Failed to find refresh token in kubeconfig"))?;
+pub async fn delete_workload(kube: &KubeClientProvider, name: &str) -> Result<()> {
+    // Deleting the TrainingWorkload also removes its pods
+    let app_config = Config::from_env();
+    let api: Api<TrainingWorkload> =
+        Api::namespaced(kube.client().await?, &app_config.kube_namespace);
+    api.delete(name, &DeleteParams::default())
+        .await
+        .inspect_err(|e| kube.forget_if_unauthorized(e))?;
 
-    // Get the idp-issuer-url from the kubeconfig for the refresh token
-    let idp_issuer_url = kubeconfig.auth_infos[0]
-        .auth_info
-        .as_ref()
-        .unwrap()
-        .auth_provider
-        .as_ref()
-        .unwrap()
-        .config
-        .get("idp-issuer-url")
-        .unwrap();
-
-    // Refresh the OIDC token
-    let new_id_token = refresh_oidc_token(&refresh_token, &idp_issuer_url).await?;
-
-    // Update the kubeconfig's auth_info
-    // Find the current context name
-    let current_context_name = kubeconfig
-        .current_context
-        .clone()
-        .ok_or_else(|| anyhow!("No current context set in kubeconfig"))?;
-
-    // Find the context that matches the current context name
-    let context = kubeconfig
-        .contexts
-        .iter()
-        .find(|ctx| ctx.name == current_context_name)
-        .ok_or_else(|| anyhow!("Failed to find current context in kubeconfig"))?;
-
-    // Unwrap the context
-    let context_context = context
-        .context
-        .as_ref()
-        .ok_or_else(|| anyhow!("Context is missing in NamedContext"))?;
+    Ok(())
+}
+
+pub async fn get_events_involving(
+    kube: &KubeClientProvider,
+    object_names: &[String],
+) -> Result<Vec<Event>> {
+    // Events of any kind of object (pods, workloads) with the given names
+    let app_config = Config::from_env();
+    let events: Api<Event> = Api::namespaced(kube.client().await?, &app_config.kube_namespace);
+
+    let mut involving = Vec::new();
+    for name in object_names {
+        let lp = ListParams::default().fields(&format!("involvedObject.name={}", name));
+        let listed = events
+            .list(&lp)
+            .await
+            .inspect_err(|e| kube.forget_if_unauthorized(e))?;
+        involving.extend(listed.items);
+    }
 
-    // Get the name of the user associated with the context
-    let auth_info_name = &context_context.user;
+    Ok(involving)
+}
 
-    // Find the auth_info with the matching name
-    let auth_info = kubeconfig
-        .auth_infos
-        .iter_mut()
-        .find(|ai| ai.name == *auth_info_name)
-        .ok_or_else(|| anyhow!("Failed to find auth_info in kubeconfig"))?;
+pub async fn get_node_gpu_type(
+    kube: &KubeClientProvider,
+    node_name: &str,
+) -> Result<Option<String>> {
+    // Set on the nodes by the NVIDIA GPU feature discovery
+    let nodes: Api<Node> = Api::all(kube.client().await?);
+    let node = nodes
+        .get(node_name)
+        .await
+        .inspect_err(|e| kube.forget_if_unauthorized(e))?;
 
-    // Unwrap the auth_info
-    let auth_info_info = auth_info
-        .auth_info
-        .as_mut()
-        .ok_or_else(|| anyhow!("AuthInfo is missing in NamedAuthInfo"))?;
+    Ok(node
+        .metadata
+        .labels
+        .and_then(|labels| labels.get(GPU_PRODUCT_LABEL).cloned()))
+}
 
-    // Remove the auth_provider and set the token
-    auth_info_info.auth_provider = None;
-    auth_info_info.token = Some(Secret::new(new_id_token));
+fn pod_names(pods: &[Arc<Pod>]) -> Vec<PodName> {
+    // Pods that cannot be attributed to a run are skipped, they are listed by
+    // the admin unrecognised workloads endpoint instead
+    pods.iter()
+        .filter_map(|pod| pod_info(pod))
+        .filter_map(|pod_info| {
+            let name = pod_info.name.clone();
+            match PodName::try_from(pod_info) {
+                Ok(pod_name) => Some(pod_name),
+                Err(e) => {
+                    println!("Skipping unrecognised pod {}: {}", name, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn get_pods(tracker: &PodTracker) -> Result<Vec<PodName>, Error> {
+    // Read the pods from the watched store rather than listing the namespace
+    Ok(pod_names(&tracker.pods()?))
+}
 
-    // Build the Kubernetes client with the updated kubeconfig
-    let config = KubeConfig::from_custom_kubeconfig(kubeconfig, &Default::default()).await?;
+pub fn get_jobs_for_submission_id(
+    tracker: &PodTracker,
+    submission_id: Uuid,
+) -> Result<Vec<PodName>> {
+    // Return an empty list if the pods have not been listed yet
+    let jobs = tracker
+        .pods_for_submission(submission_id)
+        .unwrap_or_default();
 
-    let client = Client::try_from(config)?;
-    Ok(client)
+    Ok(pod_names(&jobs))
 }
 
-pub async fn get_jobs_for_submission_id(submission_id: Uuid) -> Result<Vec<PodName>> {
-    // Get app config and kube client
-    let pods = match get_pods().await {
-        Ok(pods) => pods,
-        Err(_) => Vec::new(), // Return an empty list if there's an error
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // Filter pods by submission_id
-    let jobs: Vec<PodName> = pods
-        .into_iter()
-        .filter(|pod| pod.submission_id == submission_id)
-        .collect();
+    #[test]
+    fn keeps_valid_label_values() {
+        assert_eq!(label_value("0c3e1f2a-run_1.2"), "0c3e1f2a-run_1.2");
+    }
 
-    Ok(jobs)
+    #[test]
+    fn replaces_invalid_characters() {
+        assert_eq!(label_value("user@example.org"), "user-example.org");
+        assert_eq!(label_value("a b/c"), "a-b-c");
+    }
+
+    #[test]
+    fn trims_to_alphanumeric_ends() {
+        assert_eq!(label_value("-_user._"), "user");
+        assert_eq!(label_value("@@@"), "");
+    }
+
+    #[test]
+    fn truncates_to_63_characters() {
+        assert_eq!(label_value(&"a".repeat(70)).len(), 63);
+        assert_eq!(label_value(&format!("{}-b", "a".repeat(62))).len(), 62);
+    }
 }
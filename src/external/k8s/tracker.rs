@@ -0,0 +1,226 @@
+use super::models::{PodName, DEPLOYMENT_LABEL, SUBMISSION_ID_LABEL};
+use super::provider::KubeClientProvider;
+use super::services::{deployment_selector, pod_info};
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use futures::{StreamExt, TryStreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    runtime::{
+        reflector::{self, ObjectRef, Store},
+        watcher,
+    },
+    Api,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+// Wait before restarting the watch, after the connection to the cluster is lost
+const RESTART_DELAY_SECONDS: u64 = 5;
+
+type SubmissionIndex = HashMap<Uuid, HashSet<ObjectRef<Pod>>>;
+
+struct Snapshot {
+    store: Store<Pod>,
+    by_submission: SubmissionIndex,
+}
+
+#[derive(Clone, Copy)]
+enum Watched {
+    Labelled,
+    // Workloads submitted before they were labelled, found by their name.
+    // Only needed until the last of them has been removed from the cluster
+    Unlabelled,
+}
+
+impl Watched {
+    fn selector(self) -> String {
+        match self {
+            Watched::Labelled => deployment_selector(),
+            Watched::Unlabelled => format!("!{}", DEPLOYMENT_LABEL),
+        }
+    }
+
+    fn keeps(self, pod: &Pod) -> bool {
+        // The unlabelled pods of the namespace include those of other apps
+        match self {
+            Watched::Labelled => true,
+            Watched::Unlabelled => {
+                let prefix = format!("{}-", Config::from_env().pod_prefix);
+                pod.metadata
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| name.starts_with(&prefix))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PodTracker {
+    labelled: RwLock<Option<Snapshot>>, // None until the first listing completes
+    unlabelled: RwLock<Option<Snapshot>>,
+}
+
+fn submission_id(pod: &Pod) -> Option<Uuid> {
+    // From the label, or the name of workloads submitted before it was set
+    let labelled = pod
+        .metadata
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(SUBMISSION_ID_LABEL))
+        .and_then(|id| Uuid::parse_str(id).ok());
+    labelled.or_else(|| {
+        pod_info(pod)
+            .and_then(|pod_info| PodName::try_from(pod_info).ok())
+            .map(|pod_name| pod_name.submission_id)
+    })
+}
+
+fn index_pod(index: &mut SubmissionIndex, pod: &Pod) {
+    if let Some(submission_id) = submission_id(pod) {
+        index
+            .entry(submission_id)
+            .or_default()
+            .insert(ObjectRef::from_obj(pod));
+    }
+}
+
+fn unindex_pod(index: &mut SubmissionIndex, pod: &Pod) {
+    if let Some(submission_id) = submission_id(pod) {
+        if let Some(pods) = index.get_mut(&submission_id) {
+            pods.remove(&ObjectRef::from_obj(pod));
+            if pods.is_empty() {
+                index.remove(&submission_id);
+            }
+        }
+    }
+}
+
+impl PodTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn snapshot(&self, watched: Watched) -> &RwLock<Option<Snapshot>> {
+        match watched {
+            Watched::Labelled => &self.labelled,
+            Watched::Unlabelled => &self.unlabelled,
+        }
+    }
+
+    pub fn pods(&self) -> Result<Vec<Arc<Pod>>> {
+        // Both listings are needed, a pod missing from them is taken as
+        // removed from the cluster
+        let (labelled, unlabelled) = (
+            self.labelled.read().unwrap(),
+            self.unlabelled.read().unwrap(),
+        );
+        let (Some(labelled), Some(unlabelled)) = (labelled.as_ref(), unlabelled.as_ref()) else {
+            return Err(anyhow!("Pods have not been listed from the cluster yet"));
+        };
+
+        Ok(labelled
+            .store
+            .state()
+            .into_iter()
+            .chain(
+                unlabelled
+                    .store
+                    .state()
+                    .into_iter()
+                    .filter(|pod| Watched::Unlabelled.keeps(pod)),
+            )
+            .collect())
+    }
+
+    pub fn pods_for_submission(&self, submission_id: Uuid) -> Result<Vec<Arc<Pod>>> {
+        let (labelled, unlabelled) = (
+            self.labelled.read().unwrap(),
+            self.unlabelled.read().unwrap(),
+        );
+        let (Some(labelled), Some(unlabelled)) = (labelled.as_ref(), unlabelled.as_ref()) else {
+            return Err(anyhow!("Pods have not been listed from the cluster yet"));
+        };
+
+        Ok([labelled, unlabelled]
+            .into_iter()
+            .flat_map(|snapshot| {
+                snapshot
+                    .by_submission
+                    .get(&submission_id)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|pod| snapshot.store.get(pod))
+            })
+            .collect())
+    }
+
+    pub async fn run(&self, kube: Arc<KubeClientProvider>) {
+        tokio::join!(
+            self.keep_watching(&kube, Watched::Labelled),
+            self.keep_watching(&kube, Watched::Unlabelled)
+        );
+    }
+
+    async fn keep_watching(&self, kube: &KubeClientProvider, watched: Watched) {
+        // Keep the store in sync with the cluster, restarting the watch with a
+        // fresh client whenever it fails (ie. once the token has expired)
+        loop {
+            if let Err(e) = self.watch(kube, watched).await {
+                println!("Pod watch failed, restarting: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(RESTART_DELAY_SECONDS)).await;
+        }
+    }
+
+    async fn watch(&self, kube: &KubeClientProvider, watched: Watched) -> Result<()> {
+        let app_config = Config::from_env();
+        let client = kube.client().await?;
+        let pods: Api<Pod> = Api::namespaced(client, &app_config.kube_namespace);
+        let watcher_config = watcher::Config::default().labels(&watched.selector());
+        let slot = self.snapshot(watched);
+
+        // The previous snapshot is served until the new listing is complete
+        let (store, writer) = reflector::store();
+        let mut pending = SubmissionIndex::new();
+        let mut events = reflector::reflector(writer, watcher(pods, watcher_config)).boxed();
+        while let Some(event) = events.try_next().await.inspect_err(|e| match e {
+            watcher::Error::InitialListFailed(e)
+            | watcher::Error::WatchStartFailed(e)
+            | watcher::Error::WatchFailed(e) => kube.forget_if_unauthorized(e),
+            watcher::Error::WatchError(response) => {
+                kube.forget_if_unauthorized(&kube::Error::Api(response.clone()))
+            }
+            watcher::Error::NoResourceVersion => {}
+        })? {
+            match event {
+                watcher::Event::Init => pending.clear(),
+                watcher::Event::InitApply(pod) if watched.keeps(&pod) => {
+                    index_pod(&mut pending, &pod)
+                }
+                watcher::Event::InitDone => {
+                    *slot.write().unwrap() = Some(Snapshot {
+                        store: store.clone(),
+                        by_submission: std::mem::take(&mut pending),
+                    });
+                }
+                watcher::Event::Apply(pod) if watched.keeps(&pod) => {
+                    if let Some(snapshot) = slot.write().unwrap().as_mut() {
+                        index_pod(&mut snapshot.by_submission, &pod);
+                    }
+                }
+                watcher::Event::Delete(pod) if watched.keeps(&pod) => {
+                    if let Some(snapshot) = slot.write().unwrap().as_mut() {
+                        unindex_pod(&mut snapshot.by_submission, &pod);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Err(anyhow!("Pod watch ended"))
+    }
+}
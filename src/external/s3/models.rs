@@ -4,11 +4,11 @@ use sea_orm::FromQueryResult;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-#[derive(ToSchema, Serialize, FromQueryResult, Debug)]
+#[derive(ToSchema, Serialize, FromQueryResult, Clone, Debug)]
 pub(crate) struct OutputObject {
-    pub(super) key: String,
-    pub(super) last_modified: DateTime<Utc>,
-    pub(super) size_bytes: i64,
+    pub(crate) key: String,
+    pub(crate) last_modified: DateTime<Utc>,
+    pub(crate) size_bytes: i64,
 }
 
 impl From<aws_sdk_s3::types::Object> for OutputObject {
@@ -23,22 +23,76 @@ impl From<aws_sdk_s3::types::Object> for OutputObject {
 
 #[derive(ToSchema, Serialize, FromQueryResult, Debug)]
 pub struct OutputObjectResponse {
-    // Let's not show the full key path through the API
+    // Let's not show the full key path through the API, only the path
+    // relative to the submission's outputs
     pub filename: String,
-    pub last_modified: DateTime<Utc>,
+    pub path: String,
+    pub is_directory: bool,
+    pub last_modified: Option<DateTime<Utc>>,
     pub size_bytes: i64,
     pub url: Option<String>,
 }
 
+impl OutputObjectResponse {
+    pub fn directory(path: String) -> Self {
+        // A common prefix when browsing with a delimiter, eg. "barcode01/"
+        let filename = path
+            .trim_end_matches('/')
+            .split('/')
+            .next_back()
+            .unwrap_or_default()
+            .to_string();
+        Self {
+            filename,
+            path,
+            is_directory: true,
+            last_modified: None,
+            size_bytes: 0,
+            url: None,
+        }
+    }
+}
+
 impl From<OutputObject> for OutputObjectResponse {
     fn from(model: OutputObject) -> Self {
+        // Keys are {s3_prefix}/outputs/{submission_id}/{path}
+        let path = model
+            .key
+            .split_once("/outputs/")
+            .and_then(|(_, rest)| rest.split_once('/'))
+            .map(|(_, path)| path.to_string())
+            .unwrap_or(model.key.clone());
         // Filename is the split of the key by the last '/'
         let filename = model.key.split('/').last().unwrap().to_string();
         Self {
-            last_modified: model.last_modified,
+            last_modified: Some(model.last_modified),
             filename: filename,
+            path,
+            is_directory: false,
             size_bytes: model.size_bytes,
             url: None,
         }
     }
 }
+
+#[derive(Clone, Copy, Debug)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "application/zip",
+            ArchiveFormat::TarGz => "application/gzip",
+        }
+    }
+}
@@ -1,9 +1,28 @@
+use super::models::{ArchiveFormat, OutputObject};
 use crate::config::Config;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use async_compression::tokio::write::GzipEncoder;
+use async_zip::base::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart, StorageClass};
 use aws_sdk_s3::{config::Region, Client as S3Client};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+
+// S3 copies objects of at most 5 GiB in one request, larger ones in parts
+const MAX_COPY_BYTES: i64 = 5 * 1024 * 1024 * 1024;
+const COPY_PART_BYTES: i64 = 1024 * 1024 * 1024;
+
+// Size of the in-memory pipe between S3 and the HTTP response, which bounds
+// memory use per archive download regardless of the size of the outputs
+const ARCHIVE_BUFFER_BYTES: usize = 1024 * 1024;
 
 pub async fn get_client(config: &Config) -> Arc<S3Client> {
     let region = Region::new("us-east-1");
@@ -28,23 +47,72 @@ pub async fn get_outputs_from_submission(
     client: &Arc<S3Client>,
     obj: &crate::submissions::db::Model,
 ) -> Result<Vec<super::models::OutputObject>, Box<dyn std::error::Error>> {
+    let (outputs, _) = list_outputs(client, obj.id, None, None).await?;
+
+    Ok(outputs)
+}
+
+pub async fn list_outputs(
+    client: &Arc<S3Client>,
+    submission_id: uuid::Uuid,
+    prefix: Option<&str>,
+    delimiter: Option<&str>,
+) -> Result<(Vec<OutputObject>, Vec<String>)> {
+    // Lists the outputs under the (relative) prefix, following continuation
+    // tokens. With a delimiter, deeper keys are grouped into common prefixes
+    // which are returned separately, relative to the submission's outputs.
     let config = crate::config::Config::from_env();
-    let prefix = format!("{}/outputs/{}/", config.s3_prefix, obj.id);
-    let mut outputs: Vec<super::models::OutputObject> = vec![];
-    let list = client
-        .list_objects()
+    let outputs_prefix = format!("{}/outputs/{}/", config.s3_prefix, submission_id);
+    let mut outputs: Vec<OutputObject> = vec![];
+    let mut common_prefixes: Vec<String> = vec![];
+    let mut pages = client
+        .list_objects_v2()
         .bucket(config.s3_bucket)
-        .prefix(prefix.clone())
-        .send()
-        .await?;
+        .prefix(format!("{}{}", outputs_prefix, prefix.unwrap_or_default()))
+        .set_delimiter(delimiter.map(|d| d.to_string()))
+        .into_paginator()
+        .send();
 
-    if let Some(contents) = list.contents {
-        for object in contents {
-            outputs.push(object.into());
+    while let Some(page) = pages.next().await {
+        let page = page?;
+        for object in page.contents() {
+            outputs.push(object.clone().into());
+        }
+        for common_prefix in page.common_prefixes() {
+            if let Some(path) = common_prefix
+                .prefix()
+                .and_then(|p| p.strip_prefix(&outputs_prefix))
+            {
+                common_prefixes.push(path.to_string());
+            }
         }
     }
 
-    Ok(outputs)
+    Ok((outputs, common_prefixes))
+}
+
+pub async fn output_exists(
+    client: &Arc<S3Client>,
+    submission_id: uuid::Uuid,
+    path: &str,
+) -> Result<bool> {
+    let config = crate::config::Config::from_env();
+    match client
+        .head_object()
+        .bucket(config.s3_bucket)
+        .key(format!(
+            "{}/outputs/{}/{}",
+            config.s3_prefix, submission_id, path
+        ))
+        .send()
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(err) if matches!(err.as_service_error(), Some(HeadObjectError::NotFound(_))) => {
+            Ok(false)
+        }
+        Err(err) => Err(err.into()),
+    }
 }
 
 pub async fn delete_output_object(
@@ -61,3 +129,246 @@ pub async fn delete_output_object(
 
     Ok(())
 }
+
+pub async fn list_archived_outputs(
+    client: &Arc<S3Client>,
+    submission_id: uuid::Uuid,
+) -> Result<Vec<OutputObject>> {
+    // Archived outputs keep their path under {s3_prefix}/archive/outputs/
+    let config = crate::config::Config::from_env();
+    let mut outputs: Vec<OutputObject> = vec![];
+    let mut pages = client
+        .list_objects_v2()
+        .bucket(config.s3_bucket)
+        .prefix(format!(
+            "{}/archive/outputs/{}/",
+            config.s3_prefix, submission_id
+        ))
+        .into_paginator()
+        .send();
+
+    while let Some(page) = pages.next().await {
+        for object in page?.contents() {
+            outputs.push(object.clone().into());
+        }
+    }
+
+    Ok(outputs)
+}
+
+pub async fn archive_output_object(
+    client: &Arc<S3Client>,
+    object: &OutputObject,
+    storage_class: Option<&str>,
+) -> Result<()> {
+    // Move the output to the archive prefix, optionally in a cheaper storage
+    // class
+    let config = crate::config::Config::from_env();
+    let outputs_prefix = format!("{}/outputs/", config.s3_prefix);
+    let path = object
+        .key
+        .strip_prefix(&outputs_prefix)
+        .ok_or_else(|| anyhow::anyhow!("{} is not an output", object.key))?;
+    let archive_key = format!("{}/archive/outputs/{}", config.s3_prefix, path);
+    let copy_source = format!(
+        "{}/{}",
+        config.s3_bucket,
+        percent_encoding::utf8_percent_encode(&object.key, percent_encoding::NON_ALPHANUMERIC)
+    );
+    let storage_class = storage_class.map(StorageClass::from);
+
+    if object.size_bytes <= MAX_COPY_BYTES {
+        client
+            .copy_object()
+            .bucket(&config.s3_bucket)
+            .key(&archive_key)
+            .copy_source(&copy_source)
+            .set_storage_class(storage_class)
+            .send()
+            .await?;
+    } else {
+        let upload = client
+            .create_multipart_upload()
+            .bucket(&config.s3_bucket)
+            .key(&archive_key)
+            .set_storage_class(storage_class)
+            .send()
+            .await?;
+        let upload_id = upload
+            .upload_id
+            .ok_or_else(|| anyhow::anyhow!("S3 did not return a multipart upload ID"))?;
+
+        // Parts of an unfinished upload are kept, and billed, until aborted
+        let copied: Result<()> = async {
+            let mut parts = vec![];
+            let mut start = 0;
+            while start < object.size_bytes {
+                let end = (start + COPY_PART_BYTES).min(object.size_bytes) - 1;
+                let part_number = parts.len() as i32 + 1;
+                let part = client
+                    .upload_part_copy()
+                    .bucket(&config.s3_bucket)
+                    .key(&archive_key)
+                    .upload_id(&upload_id)
+                    .part_number(part_number)
+                    .copy_source(&copy_source)
+                    .copy_source_range(format!("bytes={}-{}", start, end))
+                    .send()
+                    .await?;
+                parts.push(
+                    CompletedPart::builder()
+                        .part_number(part_number)
+                        .set_e_tag(part.copy_part_result.and_then(|result| result.e_tag))
+                        .build(),
+                );
+                start = end + 1;
+            }
+
+            client
+                .complete_multipart_upload()
+                .bucket(&config.s3_bucket)
+                .key(&archive_key)
+                .upload_id(&upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts))
+                        .build(),
+                )
+                .send()
+                .await?;
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = copied {
+            let _ = client
+                .abort_multipart_upload()
+                .bucket(&config.s3_bucket)
+                .key(&archive_key)
+                .upload_id(&upload_id)
+                .send()
+                .await;
+            return Err(e);
+        }
+    }
+
+    client
+        .delete_object()
+        .bucket(&config.s3_bucket)
+        .key(&object.key)
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+pub fn stream_outputs_archive(
+    client: Arc<S3Client>,
+    submission_id: uuid::Uuid,
+    outputs: Vec<OutputObject>,
+    pattern: Option<glob::Pattern>,
+    format: ArchiveFormat,
+) -> impl Stream<Item = std::io::Result<Bytes>> {
+    // Objects are fetched one at a time and written into the archive as they
+    // are read, so nothing is buffered to disk or held whole in memory
+    let config = Config::from_env();
+    let prefix = format!("{}/outputs/{}/", config.s3_prefix, submission_id);
+    let entries: Vec<(String, OutputObject)> = outputs
+        .into_iter()
+        .filter_map(|object| {
+            let name = object.key.strip_prefix(&prefix)?.to_string();
+            match &pattern {
+                Some(pattern) if !pattern.matches(&name) => None,
+                _ => Some((name, object)),
+            }
+        })
+        .collect();
+
+    let (writer, reader) = tokio::io::duplex(ARCHIVE_BUFFER_BYTES);
+    let archived = tokio::spawn(async move {
+        match format {
+            ArchiveFormat::Zip => write_zip(&client, &config, entries, writer).await,
+            ArchiveFormat::TarGz => write_tar_gz(&client, &config, entries, writer).await,
+        }
+    });
+
+    // The response has already started when an object fails, so the error
+    // ends the body stream to abort the connection, rather than letting the
+    // client take a truncated archive for a complete one
+    let failure = futures::stream::once(async move {
+        let error = match archived.await {
+            Ok(Ok(())) => return None,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => e.to_string(),
+        };
+        println!("Failed to stream outputs of {}: {}", submission_id, error);
+        Some(Err(std::io::Error::other(error)))
+    })
+    .filter_map(futures::future::ready);
+
+    ReaderStream::new(reader).chain(failure)
+}
+
+async fn get_object_body(
+    client: &S3Client,
+    config: &Config,
+    key: &str,
+) -> Result<impl tokio::io::AsyncBufRead + Unpin> {
+    let object = client
+        .get_object()
+        .bucket(&config.s3_bucket)
+        .key(key)
+        .send()
+        .await?;
+
+    Ok(object.body.into_async_read())
+}
+
+async fn write_zip(
+    client: &S3Client,
+    config: &Config,
+    entries: Vec<(String, OutputObject)>,
+    writer: DuplexStream,
+) -> Result<()> {
+    // Outputs are mostly already compressed (fastq.gz, bam), so store them
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    for (name, object) in entries {
+        let body = get_object_body(client, config, &object.key).await?;
+        let entry = ZipEntryBuilder::new(name.into(), Compression::Stored);
+        let mut entry_writer = zip.write_entry_stream(entry).await?;
+        futures::io::copy(body.compat(), &mut entry_writer).await?;
+        entry_writer.close().await?;
+    }
+    zip.close().await?;
+
+    Ok(())
+}
+
+async fn write_tar_gz(
+    client: &S3Client,
+    config: &Config,
+    entries: Vec<(String, OutputObject)>,
+    writer: impl AsyncWrite + Unpin + Send + 'static,
+) -> Result<()> {
+    let mut tar = tokio_tar::Builder::new(GzipEncoder::new(writer));
+    for (name, object) in entries {
+        // The header is written with the listed size, an object that has
+        // changed since would leave the entry corrupt
+        let mut body = get_object_body(client, config, &object.key)
+            .await?
+            .take(object.size_bytes as u64);
+        let mut header = tokio_tar::Header::new_gnu();
+        header.set_size(object.size_bytes as u64);
+        header.set_mode(0o644);
+        header.set_mtime(object.last_modified.timestamp() as u64);
+        tar.append_data(&mut header, &name, &mut body).await?;
+        if body.limit() > 0 || body.into_inner().read(&mut [0u8]).await? > 0 {
+            return Err(anyhow!("{} changed size while being archived", name));
+        }
+    }
+    let mut encoder = tar.into_inner().await?;
+    encoder.shutdown().await?;
+
+    Ok(())
+}
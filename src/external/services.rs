@@ -2,11 +2,12 @@ use super::db::{ActiveModel, Entity};
 use super::models::ServiceCreate;
 use crate::config::Config;
 use crate::external::db::ServiceName;
+use crate::external::k8s::tracker::PodTracker;
 use anyhow::{anyhow, Result};
 use sea_orm::{Database, DatabaseConnection, EntityTrait};
 
-async fn check_kubernetes() -> Result<serde_json::Value> {
-    match crate::external::k8s::services::get_pods().await {
+async fn check_kubernetes(tracker: &PodTracker) -> Result<serde_json::Value> {
+    match crate::external::k8s::services::get_pods(tracker) {
         Ok(pods) => Ok(serde_json::to_value(pods).unwrap()),
         Err(err) => Err(anyhow!(serde_json::to_value(err.to_string()).unwrap())),
     }
@@ -26,13 +27,13 @@ async fn check_s3(config: &Config) -> Result<serde_json::Value> {
     }
 }
 
-pub async fn check_external_services() {
+pub async fn check_external_services(tracker: &PodTracker) {
     let config = Config::from_env();
     let db: DatabaseConnection = Database::connect(&*config.db_url.as_ref().unwrap())
         .await
         .unwrap();
 
-    let k8s: ActiveModel = match check_kubernetes().await {
+    let k8s: ActiveModel = match check_kubernetes(tracker).await {
         Ok(pods) => ServiceCreate {
             service_name: ServiceName::RCP,
             is_online: true,
@@ -1,22 +1,269 @@
-use super::models::{ChangeFileInfo, PreCreateResponse};
-use crate::external::tus::models::{EventPayload, HttpResponse};
+use super::models::{ChangeFileInfo, Header, PreCreateResponse, Upload};
+use crate::common::auth::Role;
+use crate::external::tus::models::EventPayload;
 use crate::submissions::db as SubmissionDB;
+use crate::submissions::events::models::{SubmissionEvent, UploadEvent, UploadState};
+use crate::submissions::events::services::EventHub;
 use crate::uploads::associations::db as AssociationDB;
 use crate::uploads::db as InputObjectDB;
+use crate::uploads::partials::db as PartialDB;
 use anyhow::Result;
 use aws_sdk_s3::Client as S3Client;
+use axum_keycloak_auth::{decode::KeycloakToken, layer::KeycloakAuthLayer};
 use chrono::Utc;
 use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, Set};
+use sea_query::Expr;
 use std::sync::Arc;
 use uuid::Uuid;
 
+async fn authenticate_user(
+    auth: &KeycloakAuthLayer<Role>,
+    header: &Header,
+) -> Result<KeycloakToken<Role>> {
+    // tusd forwards the headers of the user's request, validate the bearer
+    // token they sent to tusd to identify them
+    let authorization = header
+        .authorization
+        .as_ref()
+        .and_then(|values| values.first())
+        .ok_or_else(|| anyhow::anyhow!("Authorization header not found"))?;
+    let raw_token = authorization
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| anyhow::anyhow!("Authorization header is not a bearer token"))?;
+
+    match auth.validate_raw_token(raw_token).await {
+        Ok((_, token)) => Ok(token),
+        Err(err) => Err(anyhow::anyhow!("Invalid token: {}", err)),
+    }
+}
+
+fn parse_object_id(upload_id: &str) -> Result<Uuid> {
+    // Split the upload_id on the + separator to get the object ID.
+    upload_id
+        .split('+')
+        .next()
+        .and_then(|id_str| Uuid::parse_str(id_str).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid object ID in upload_id"))
+}
+
+fn upload_progress_message(upload: &Upload) -> String {
+    // Deferred length uploads do not know their size until the last part
+    if upload.size_is_deferred || upload.size <= 0 {
+        format!("Upload progress: {} bytes received", upload.offset)
+    } else {
+        let uploaded_percentage = (upload.offset as f64 / upload.size as f64) * 100.0;
+        format!("Upload progress: {:.2}%", uploaded_percentage)
+    }
+}
+
+async fn publish_upload_event(
+    db: &DatabaseConnection,
+    hub: &EventHub,
+    upload: &InputObjectDB::Model,
+    state: UploadState,
+) -> Result<()> {
+    // Find the submission the upload belongs to, to notify its listeners.
+    // Events are informational, so a missing association does not fail the hook
+    let submission_id = match AssociationDB::Entity::find()
+        .filter(AssociationDB::Column::InputObjectId.eq(upload.id))
+        .one(db)
+        .await?
+    {
+        Some(association) => association.submission_id,
+        None => {
+            println!(
+                "Upload {} is not associated with a submission, no event sent",
+                upload.id
+            );
+            return Ok(());
+        }
+    };
+
+    hub.publish(SubmissionEvent::Upload(UploadEvent::from_upload(
+        submission_id,
+        upload,
+        state,
+    )));
+
+    Ok(())
+}
+
+async fn exceeds_quota_once_sized(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+    counted_bytes: i64,
+    upload: &Upload,
+) -> Result<bool> {
+    // Deferred length uploads are accepted as 0 bytes, so the quota is only
+    // checked once their length is declared. What is already counted in the
+    // usage is not counted twice.
+    if upload.size_is_deferred || upload.size <= counted_bytes {
+        return Ok(false);
+    }
+
+    let submission = SubmissionDB::Entity::find_by_id(submission_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Submission not found"))?;
+    let size_bytes = upload.size - counted_bytes;
+
+    // Submissions from before their creator was recorded have no owner whose
+    // quota applies, only the project quota does
+    match submission.created_by.as_deref() {
+        Some(owner) => Ok(!crate::quota::services::get_quota(db, owner)
+            .await?
+            .allows(size_bytes)),
+        None => Ok(!crate::quota::services::get_project_quota(db)
+            .await?
+            .allows(size_bytes)),
+    }
+}
+
+async fn handle_partial_pre_create(
+    db: &DatabaseConnection,
+    hub: &EventHub,
+    token: &KeycloakToken<Role>,
+    submission_id: Uuid,
+    upload: &Upload,
+) -> Result<PreCreateResponse> {
+    let quota = crate::quota::services::get_quota(db, &token.subject).await?;
+    if !quota.allows(upload.size) {
+        return Ok(PreCreateResponse::reject(
+            413,
+            "Upload exceeds the storage quota",
+        ));
+    }
+
+    let partial = PartialDB::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        submission_id: Set(submission_id),
+        final_object_id: Set(None),
+        created_on: Set(Utc::now().naive_utc()),
+        size_bytes: Set(upload.size),
+        size_is_deferred: Set(upload.size_is_deferred),
+        bytes_received: Set(0),
+        all_parts_received: Set(false),
+        last_part_received: Set(Some(Utc::now().naive_utc())),
+    };
+
+    let partial = match PartialDB::Entity::insert(partial)
+        .exec_with_returning(db)
+        .await
+    {
+        Ok(partial) => partial,
+        _ => return Err(anyhow::anyhow!("Failed to create partial upload")),
+    };
+
+    hub.publish(SubmissionEvent::Upload(UploadEvent::from_partial(
+        &partial,
+        UploadState::Created,
+    )));
+
+    Ok(PreCreateResponse {
+        change_file_info: Some(ChangeFileInfo {
+            id: partial.id.to_string(),
+        }),
+        status: "success".to_string(),
+        ..Default::default()
+    })
+}
+
+async fn update_partial_upload(
+    db: &DatabaseConnection,
+    hub: &EventHub,
+    upload: &Upload,
+    finished: bool,
+) -> Result<PreCreateResponse> {
+    let partial_id = parse_object_id(&upload.id)?;
+
+    let partial = PartialDB::Entity::find_by_id(partial_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Failed to find partial upload"))?;
+    let counted_bytes = partial.size_bytes.max(partial.bytes_received);
+    // Both the pre-finish and post-finish hooks finish an upload
+    let already_completed = partial.all_parts_received;
+    if !finished
+        && exceeds_quota_once_sized(db, partial.submission_id, counted_bytes, upload).await?
+    {
+        return Ok(PreCreateResponse::stop(
+            413,
+            "Upload exceeds the storage quota",
+        ));
+    }
+    let mut partial: PartialDB::ActiveModel = partial.into();
+
+    if !upload.size_is_deferred {
+        partial.size_bytes = Set(upload.size);
+        partial.size_is_deferred = Set(false);
+    }
+    partial.bytes_received = Set(upload.offset as i64);
+    partial.last_part_received = Set(Some(Utc::now().naive_utc()));
+    if finished {
+        partial.size_bytes = Set(upload.offset as i64);
+        partial.all_parts_received = Set(true);
+    }
+
+    match PartialDB::Entity::update(partial).exec(db).await {
+        Ok(partial) => {
+            let state = if finished {
+                UploadState::Completed
+            } else {
+                UploadState::Receiving
+            };
+            if !(finished && already_completed) {
+                hub.publish(SubmissionEvent::Upload(UploadEvent::from_partial(
+                    &partial, state,
+                )));
+            }
+
+            Ok(PreCreateResponse {
+                change_file_info: None,
+                status: "Partial upload updated".to_string(),
+                ..Default::default()
+            })
+        }
+        _ => Err(anyhow::anyhow!("Failed to update partial upload")),
+    }
+}
+
+async fn delete_partial_uploads(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    final_object_id: Uuid,
+) -> Result<()> {
+    // Once the final upload is complete its partial uploads have been
+    // concatenated into it and are no longer needed
+    let config = crate::config::Config::from_env();
+    let partials = PartialDB::Entity::find()
+        .filter(PartialDB::Column::FinalObjectId.eq(final_object_id))
+        .all(db)
+        .await?;
+
+    for partial in partials {
+        s3.delete_object()
+            .bucket(&config.s3_bucket)
+            .key(format!("{}/{}", config.s3_prefix, partial.id))
+            .send()
+            .await?;
+        partial.delete(db).await?;
+    }
+
+    Ok(())
+}
+
 pub(super) async fn handle_pre_create(
     db: DatabaseConnection,
     s3: Arc<S3Client>,
+    auth: Arc<KeycloakAuthLayer<Role>>,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
-    let filename = payload.event.upload.metadata.filename;
-    let filetype = payload.event.upload.metadata.filetype;
+    let token = match authenticate_user(&auth, &payload.event.http_request.header).await {
+        Ok(token) => token,
+        Err(_) => return Ok(PreCreateResponse::reject(401, "Unauthorized")),
+    };
+
     let size_in_bytes = payload.event.upload.size;
     let submission_id: Uuid = match payload.event.http_request.header.submission_id {
         Some(submission_id) => match submission_id.get(0).unwrap().parse() {
@@ -26,89 +273,126 @@ pub(super) async fn handle_pre_create(
         _ => Err(anyhow::anyhow!("Submission ID not found"))?,
     };
 
-    // Check that the submission does not already have that same filename
-    let results: Vec<(SubmissionDB::Model, Vec<InputObjectDB::Model>)> =
-        SubmissionDB::Entity::find()
-            .filter(SubmissionDB::Column::Id.eq(submission_id))
-            .find_with_related(InputObjectDB::Entity)
-            .filter(InputObjectDB::Column::Filename.eq(filename.clone()))
-            .all(&db)
-            .await
-            .unwrap();
-
-    // Unpack the tuples to check if filename is already in use
-    for (_, objs) in results.iter() {
-        if !objs.is_empty() {
-            let existing_object = &objs[0];
-            if existing_object.all_parts_received {
-                // File upload is complete, return 400 error
-                return Ok(PreCreateResponse {
-                    status: "failure".to_string(),
-                    http_response: Some(HttpResponse {
-                        status_code: Some(400),
-                        body: Some(
-                            "File already uploaded with this filename in this submission"
-                                .to_string(),
-                        ),
-                        ..Default::default()
-                    }),
-                    reject_upload: true,
-                    ..Default::default()
-                });
-            } else {
-                // File upload is incomplete, delete from S3 and DB
-                crate::uploads::services::delete_object(&db, &s3, existing_object.id).await?;
-                existing_object.clone().delete(&db).await?;
+    // Only allow uploads to submissions the user has access to
+    match SubmissionDB::Entity::find_by_id(submission_id)
+        .one(&db)
+        .await?
+    {
+        Some(submission) => {
+            if !crate::submissions::services::user_can_access(&token, &submission) {
+                return Ok(PreCreateResponse::reject(
+                    403,
+                    "Not allowed to upload to this submission",
+                ));
             }
         }
+        None => return Ok(PreCreateResponse::reject(404, "Submission not found")),
+    }
+
+    // Partial uploads are only tracked until a final upload joins them
+    if payload.event.upload.is_partial {
+        return handle_partial_pre_create(&db, &hub, &token, submission_id, &payload.event.upload)
+            .await;
+    }
+
+    // The partial uploads that a final upload concatenates must belong to
+    // the same submission
+    let partial_ids: Vec<Uuid> = match &payload.event.upload.partial_uploads {
+        Some(upload_ids) if payload.event.upload.is_final => upload_ids
+            .iter()
+            .map(|upload_id| parse_object_id(upload_id))
+            .collect::<Result<Vec<Uuid>>>()?,
+        _ => vec![],
+    };
+    if !partial_ids.is_empty() {
+        let partials = PartialDB::Entity::find()
+            .filter(PartialDB::Column::Id.is_in(partial_ids.clone()))
+            .filter(PartialDB::Column::SubmissionId.eq(submission_id))
+            .all(&db)
+            .await?;
+        if partials.len() != partial_ids.len() {
+            return Ok(PreCreateResponse::reject(
+                400,
+                "Partial uploads do not belong to this submission",
+            ));
+        }
+    }
+
+    let filename = payload.event.upload.metadata.filename;
+    let filetype = payload.event.upload.metadata.filetype;
+    let relative_path = crate::uploads::services::normalise_relative_path(
+        payload.event.upload.metadata.relative_path.as_deref(),
+        &filename,
+    )?;
+
+    // Check that the submission does not already have a file at that same path
+    if !crate::uploads::services::free_upload_path(&db, &s3, submission_id, &relative_path).await? {
+        // File upload is complete, return 400 error
+        return Ok(PreCreateResponse::reject(
+            400,
+            "File already uploaded with this path in this submission",
+        ));
     }
 
     // Proceed if no complete files are found
     let allowed_types: Vec<&str> = vec!["application/octet-stream"];
-    let allowed_file_extensions: Vec<&str> = vec!["pod5"];
 
     if !allowed_types.contains(&filetype.as_str()) {
         return Err(anyhow::anyhow!("Filetype not allowed"));
     }
-    if !allowed_file_extensions.contains(&filename.split('.').last().unwrap()) {
+    if !crate::uploads::services::has_allowed_extension(&filename) {
         return Err(anyhow::anyhow!("File extension not allowed"));
     }
 
-    // Create new object in DB
-    let object = InputObjectDB::ActiveModel {
-        id: Set(Uuid::new_v4()),
-        created_on: Set(Utc::now().naive_utc()),
-        filename: Set(filename.clone()),
-        size_bytes: Set(size_in_bytes),
-        all_parts_received: Set(false),
-        last_part_received: Set(Some(Utc::now().naive_utc())),
-        processing_message: Set(Some("Upload initiated".to_string())),
-        ..Default::default()
-    };
-
-    let object = match InputObjectDB::Entity::insert(object).exec(&db).await {
-        Ok(obj) => obj,
-        _ => return Err(anyhow::anyhow!("Failed to create object")),
-    };
+    // Reject the upload if it would take the user or project over quota. The
+    // size of deferred length uploads is declared as 0, they are checked again
+    // in the post-receive hook once their length is known.
+    let quota = crate::quota::services::get_quota(&db, &token.subject).await?;
+    if !quota.allows(size_in_bytes) {
+        return Ok(PreCreateResponse::reject(
+            413,
+            "Upload exceeds the storage quota",
+        ));
+    }
 
-    let association_object = AssociationDB::ActiveModel {
-        input_object_id: Set(object.last_insert_id),
-        submission_id: Set(submission_id),
-        ..Default::default()
+    // Create new object in DB, another upload may have taken the path since
+    let object = match crate::uploads::services::create_object(
+        &db,
+        submission_id,
+        filename,
+        relative_path,
+        size_in_bytes,
+    )
+    .await
+    {
+        Ok(object) => object,
+        Err(e) if crate::uploads::services::is_path_conflict(&e) => {
+            return Ok(PreCreateResponse::reject(
+                409,
+                "File already uploaded with this path in this submission",
+            ))
+        }
+        Err(e) => return Err(e),
     };
 
-    match AssociationDB::Entity::insert(association_object)
-        .exec(&db)
-        .await
-    {
-        Ok(_) => (),
-        _ => return Err(anyhow::anyhow!("Failed to create association")),
+    if !partial_ids.is_empty() {
+        PartialDB::Entity::update_many()
+            .col_expr(PartialDB::Column::FinalObjectId, Expr::value(object.id))
+            .filter(PartialDB::Column::Id.is_in(partial_ids))
+            .exec(&db)
+            .await?;
     }
 
+    hub.publish(SubmissionEvent::Upload(UploadEvent::from_upload(
+        submission_id,
+        &object,
+        UploadState::Created,
+    )));
+
     // Respond with a custom ID for tusd to upload to S3
     Ok(PreCreateResponse {
         change_file_info: Some(ChangeFileInfo {
-            id: object.last_insert_id.to_string(),
+            id: object.id.to_string(),
         }),
         status: "success".to_string(),
         ..Default::default()
@@ -117,8 +401,13 @@ pub(super) async fn handle_pre_create(
 
 pub(super) async fn handle_post_create(
     db: DatabaseConnection,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
+    if payload.event.upload.is_partial {
+        return update_partial_upload(&db, &hub, &payload.event.upload, false).await;
+    }
+
     let upload_id = &payload.event.upload.id;
     // Split the upload_id on the + separator to get the object ID.
     let object_id: Uuid = match upload_id
@@ -148,19 +437,27 @@ pub(super) async fn handle_post_create(
 
     // let obj: db::Model = db::Entity::update(obj).exec(&db).await.unwrap();
     match InputObjectDB::Entity::update(obj).exec(&db).await {
-        Ok(_) => Ok(PreCreateResponse {
-            change_file_info: None,
-            status: "Upload accepted".to_string(),
-            ..Default::default()
-        }),
+        Ok(obj) => {
+            publish_upload_event(&db, &hub, &obj, UploadState::Receiving).await?;
+            Ok(PreCreateResponse {
+                change_file_info: None,
+                status: "Upload accepted".to_string(),
+                ..Default::default()
+            })
+        }
         _ => Err(anyhow::anyhow!("Failed to update after upload started")),
     }
 }
 
 pub(super) async fn handle_post_receive(
     db: DatabaseConnection,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
+    if payload.event.upload.is_partial {
+        return update_partial_upload(&db, &hub, &payload.event.upload, false).await;
+    }
+
     let upload_id = &payload.event.upload.id;
     // Split the upload_id on the + separator to get the object ID.
     let object_id: Uuid = match upload_id
@@ -174,10 +471,6 @@ pub(super) async fn handle_post_receive(
         }
     };
 
-    let size_in_bytes = payload.event.upload.size;
-    let offset = payload.event.upload.offset;
-    let uploaded_percentage = (offset as f64 / size_in_bytes as f64) * 100.0;
-
     let obj = match InputObjectDB::Entity::find()
         .filter(InputObjectDB::Column::Id.eq(object_id))
         .one(&db)
@@ -195,28 +488,53 @@ pub(super) async fn handle_post_receive(
             ..Default::default()
         });
     }
-    let mut obj: InputObjectDB::ActiveModel = obj.unwrap().into();
+    let obj = obj.unwrap();
 
-    obj.processing_message = Set(Some(
-        format!("Upload progress: {:.2}%", uploaded_percentage).to_owned(),
-    ));
+    let submission_id = AssociationDB::Entity::find()
+        .filter(AssociationDB::Column::InputObjectId.eq(obj.id))
+        .one(&db)
+        .await?
+        .map(|association| association.submission_id)
+        .ok_or_else(|| anyhow::anyhow!("Upload is not associated with a submission"))?;
+    if exceeds_quota_once_sized(&db, submission_id, obj.size_bytes, &payload.event.upload).await? {
+        return Ok(PreCreateResponse::stop(
+            413,
+            "Upload exceeds the storage quota",
+        ));
+    }
+
+    let mut obj: InputObjectDB::ActiveModel = obj.into();
+    obj.processing_message = Set(Some(upload_progress_message(&payload.event.upload)));
+    obj.bytes_received = Set(payload.event.upload.offset as i64);
+    // Deferred length uploads declare their size with a later part
+    if !payload.event.upload.size_is_deferred {
+        obj.size_bytes = Set(payload.event.upload.size);
+    }
     obj.last_part_received = Set(Some(Utc::now().naive_utc().to_owned()));
 
     // let obj: db::Model = db::Entity::update(obj).exec(&db).await.unwrap();
     match InputObjectDB::Entity::update(obj).exec(&db).await {
-        Ok(_) => Ok(PreCreateResponse {
-            change_file_info: None,
-            status: "Upload progress updated".to_string(),
-            ..Default::default()
-        }),
+        Ok(obj) => {
+            publish_upload_event(&db, &hub, &obj, UploadState::Receiving).await?;
+            Ok(PreCreateResponse {
+                change_file_info: None,
+                status: "Upload progress updated".to_string(),
+                ..Default::default()
+            })
+        }
         _ => Err(anyhow::anyhow!("Failed to update upload progress")),
     }
 }
 
 pub(super) async fn handle_pre_finish(
     db: DatabaseConnection,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
+    if payload.event.upload.is_partial {
+        return update_partial_upload(&db, &hub, &payload.event.upload, true).await;
+    }
+
     let upload_id = &payload.event.upload.id;
     // Split the upload_id on the + separator to get the object ID.
     let object_id: Uuid = match upload_id
@@ -243,22 +561,33 @@ pub(super) async fn handle_pre_finish(
 
     obj.processing_message = Set(Some("Upload completed".to_owned()));
     obj.all_parts_received = Set(true);
+    obj.size_bytes = Set(payload.event.upload.offset as i64);
+    obj.bytes_received = Set(payload.event.upload.offset as i64);
     obj.last_part_received = Set(Some(Utc::now().naive_utc().to_owned()));
 
     match InputObjectDB::Entity::update(obj).exec(&db).await {
-        Ok(_) => Ok(PreCreateResponse {
-            change_file_info: None,
-            status: "Upload completed".to_string(),
-            ..Default::default()
-        }),
+        Ok(obj) => {
+            publish_upload_event(&db, &hub, &obj, UploadState::Completed).await?;
+            Ok(PreCreateResponse {
+                change_file_info: None,
+                status: "Upload completed".to_string(),
+                ..Default::default()
+            })
+        }
         _ => Err(anyhow::anyhow!("Failed to update after upload completed")),
     }
 }
 
 pub(super) async fn handle_post_finish(
     db: DatabaseConnection,
+    s3: Arc<S3Client>,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
+    if payload.event.upload.is_partial {
+        return update_partial_upload(&db, &hub, &payload.event.upload, true).await;
+    }
+
     let upload_id = &payload.event.upload.id;
 
     // Split the upload_id on the + separator to get the object ID.
@@ -282,27 +611,57 @@ pub(super) async fn handle_post_finish(
         _ => return Err(anyhow::anyhow!("Failed to find object")),
     };
 
-    let mut obj: InputObjectDB::ActiveModel = obj.unwrap().into();
+    let obj = obj.ok_or_else(|| anyhow::anyhow!("Failed to find object"))?;
+    // Completed is only sent here if the pre-finish hook did not already
+    let already_completed = obj.all_parts_received;
+    let mut obj: InputObjectDB::ActiveModel = obj.into();
 
     obj.processing_message = Set(Some("Upload completed".to_owned()));
     obj.all_parts_received = Set(true);
+    obj.size_bytes = Set(payload.event.upload.offset as i64);
+    obj.bytes_received = Set(payload.event.upload.offset as i64);
     obj.last_part_received = Set(Some(Utc::now().naive_utc().to_owned()));
 
-    match InputObjectDB::Entity::update(obj).exec(&db).await {
-        Ok(_) => Ok(PreCreateResponse {
-            change_file_info: None,
-            status: "Upload completed".to_string(),
-            ..Default::default()
-        }),
-        _ => Err(anyhow::anyhow!("Failed to update after upload completed")),
+    let obj = match InputObjectDB::Entity::update(obj).exec(&db).await {
+        Ok(obj) => obj,
+        _ => return Err(anyhow::anyhow!("Failed to update after upload completed")),
+    };
+    if !already_completed {
+        publish_upload_event(&db, &hub, &obj, UploadState::Completed).await?;
+    }
+
+    if payload.event.upload.is_final {
+        delete_partial_uploads(&db, &s3, object_id).await?;
     }
+
+    Ok(PreCreateResponse {
+        change_file_info: None,
+        status: "Upload completed".to_string(),
+        ..Default::default()
+    })
 }
 
 pub(super) async fn handle_post_terminate(
     db: DatabaseConnection,
+    hub: Arc<EventHub>,
     payload: EventPayload,
 ) -> Result<PreCreateResponse> {
     // This hook is sent when the file should be cleaned up (del from db)
+    if payload.event.upload.is_partial {
+        let partial_id = parse_object_id(&payload.event.upload.id)?;
+        if let Some(partial) = PartialDB::Entity::find_by_id(partial_id).one(&db).await? {
+            hub.publish(SubmissionEvent::Upload(UploadEvent::from_partial(
+                &partial,
+                UploadState::Terminated,
+            )));
+            partial.delete(&db).await?;
+        }
+        return Ok(PreCreateResponse {
+            change_file_info: None,
+            status: "Upload terminated".to_string(),
+            ..Default::default()
+        });
+    }
 
     let upload_id = &payload.event.upload.id;
 
@@ -327,6 +686,10 @@ pub(super) async fn handle_post_terminate(
         _ => return Err(anyhow::anyhow!("Failed to find object")),
     };
 
+    let obj = obj.unwrap();
+    // Notify before the association to the submission is removed
+    publish_upload_event(&db, &hub, &obj, UploadState::Terminated).await?;
+
     // Delete all associations, then delete the object
     AssociationDB::Entity::delete_many()
         .filter(AssociationDB::Column::InputObjectId.eq(object_id))
@@ -334,7 +697,6 @@ pub(super) async fn handle_post_terminate(
         .await
         .unwrap();
 
-    let obj = obj.unwrap();
     match obj.delete(&db).await {
         Ok(_) => Ok(PreCreateResponse {
             change_file_info: None,
@@ -52,6 +52,8 @@ pub struct Header {
     pub accept_encoding: Option<Vec<String>>,
     #[serde(rename = "Accept-Language")]
     pub accept_language: Option<Vec<String>>,
+    #[serde(rename = "Authorization")]
+    pub authorization: Option<Vec<String>>,
     #[serde(rename = "Cache-Control")]
     pub cache_control: Option<Vec<String>>,
     #[serde(rename = "Content-Length")]
@@ -103,7 +105,7 @@ pub struct Upload {
     #[serde(rename = "Offset")]
     pub offset: u64,
     #[serde(rename = "PartialUploads")]
-    pub partial_uploads: Option<String>,
+    pub partial_uploads: Option<Vec<String>>,
     #[serde(rename = "Size")]
     pub size: i64,
     #[serde(rename = "SizeIsDeferred")]
@@ -112,6 +114,7 @@ pub struct Upload {
     pub storage: Option<Storage>,
 }
 #[derive(Debug, Deserialize, Serialize)]
+#[serde(default)] // Partial uploads are created without metadata
 pub struct MetaData {
     #[serde(rename = "filename")]
     pub filename: String,
@@ -198,6 +201,8 @@ pub struct PreCreateResponse {
     pub status: String,
     #[serde(rename = "RejectUpload")]
     pub reject_upload: bool,
+    #[serde(rename = "StopUpload")]
+    pub stop_upload: bool, // Only honoured by tusd in the post-receive hook
 }
 
 impl Default for PreCreateResponse {
@@ -207,6 +212,37 @@ impl Default for PreCreateResponse {
             http_response: None,
             status: String::new(),
             reject_upload: false,
+            stop_upload: false,
+        }
+    }
+}
+
+impl PreCreateResponse {
+    // Response that makes tusd reject the upload with the given HTTP status
+    pub fn reject(status_code: u64, body: &str) -> Self {
+        Self {
+            status: "failure".to_string(),
+            http_response: Some(HttpResponse {
+                status_code: Some(status_code),
+                body: Some(body.to_string()),
+                ..Default::default()
+            }),
+            reject_upload: true,
+            ..Default::default()
+        }
+    }
+
+    // Response that makes tusd stop and terminate an upload in progress
+    pub fn stop(status_code: u64, body: &str) -> Self {
+        Self {
+            status: "failure".to_string(),
+            http_response: Some(HttpResponse {
+                status_code: Some(status_code),
+                body: Some(body.to_string()),
+                ..Default::default()
+            }),
+            stop_upload: true,
+            ..Default::default()
         }
     }
 }
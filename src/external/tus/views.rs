@@ -6,7 +6,15 @@ use crate::external::tus::models::{EventPayload, EventType};
 // use crate::objects::models::InputObject;
 use super::models::PreCreateResponse;
 use crate::common::auth::Role;
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use crate::submissions::events::services::EventHub;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::Response,
+    routing::post,
+    Extension, Json, Router,
+};
 use axum_keycloak_auth::{
     instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
 };
@@ -15,34 +23,71 @@ use aws_sdk_s3::Client as S3Client;
 use sea_orm::DatabaseConnection;
 use std::sync::Arc;
 
+// Header tusd is configured to send the shared secret in
+const HOOK_SECRET_HEADER: &str = "Hook-Secret";
+
 pub fn router(
     db: DatabaseConnection,
     keycloak_auth_instance: Arc<KeycloakAuthInstance>,
     s3: Arc<S3Client>,
+    event_hub: Arc<EventHub>,
 ) -> Router {
+    // Used to validate the user's token forwarded by tusd, any authenticated
+    // user may upload to the submissions they have access to
+    let user_auth: Arc<KeycloakAuthLayer<Role>> = Arc::new(
+        KeycloakAuthLayer::<Role>::builder()
+            .instance(keycloak_auth_instance)
+            .passthrough_mode(PassthroughMode::Block)
+            .persist_raw_claims(false)
+            .expected_audiences(vec![String::from("account")])
+            .build(),
+    );
+
     Router::new()
         .route("/hooks", post(handle_tus_hooks))
-        .with_state((db, s3))
-        // Add the KeycloakAuthLayer to validate JWT tokens for tus hooks
-        .layer(
-            KeycloakAuthLayer::<Role>::builder()
-                .instance(keycloak_auth_instance)
-                .passthrough_mode(PassthroughMode::Block)
-                .persist_raw_claims(false)
-                .expected_audiences(vec![String::from("account")])
-                .required_roles(vec![Role::Administrator]) // Only allow admin roles
-                .build(),
-        )
+        .with_state((db, s3, user_auth))
+        .layer(Extension(event_hub))
+        // Hook calls come from tusd, authenticate them with the shared secret
+        .layer(middleware::from_fn(verify_hook_secret))
+}
+
+async fn verify_hook_secret(request: Request, next: Next) -> Result<Response, StatusCode> {
+    let config = crate::config::Config::from_env();
+
+    let secret = request
+        .headers()
+        .get(HOOK_SECRET_HEADER)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+
+    if !constant_time_eq(secret, config.tus_hook_secret.as_bytes()) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(next.run(request).await)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    // Compare every byte so the time taken does not leak the secret
+    if a.len() != b.len() || b.is_empty() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 // Example of async function to handle tus hook events
 #[axum::debug_handler]
 pub async fn handle_tus_hooks(
-    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    State((db, s3, auth)): State<(
+        DatabaseConnection,
+        Arc<S3Client>,
+        Arc<KeycloakAuthLayer<Role>>,
+    )>,
+    Extension(hub): Extension<Arc<EventHub>>,
     Json(payload): Json<EventPayload>,
 ) -> (StatusCode, Json<PreCreateResponse>) {
     match payload.event_type {
-        EventType::PreCreate => match handle_pre_create(db, s3, payload).await {
+        EventType::PreCreate => match handle_pre_create(db, s3, auth, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -53,7 +98,7 @@ pub async fn handle_tus_hooks(
                 }),
             ),
         },
-        EventType::PostReceive => match handle_post_receive(db, payload).await {
+        EventType::PostReceive => match handle_post_receive(db, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -64,7 +109,7 @@ pub async fn handle_tus_hooks(
                 }),
             ),
         },
-        EventType::PostCreate => match handle_post_create(db, payload).await {
+        EventType::PostCreate => match handle_post_create(db, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -75,7 +120,7 @@ pub async fn handle_tus_hooks(
                 }),
             ),
         },
-        EventType::PreFinish => match handle_pre_finish(db, payload).await {
+        EventType::PreFinish => match handle_pre_finish(db, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -87,7 +132,7 @@ pub async fn handle_tus_hooks(
             ),
         },
 
-        EventType::PostFinish => match handle_post_finish(db, payload).await {
+        EventType::PostFinish => match handle_post_finish(db, s3, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -98,7 +143,7 @@ pub async fn handle_tus_hooks(
                 }),
             ),
         },
-        EventType::PostTerminate => match handle_post_terminate(db, payload).await {
+        EventType::PostTerminate => match handle_post_terminate(db, hub, payload).await {
             Ok(response) => (StatusCode::CREATED, Json(response)),
             Err(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
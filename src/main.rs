@@ -1,11 +1,19 @@
+mod admin;
 mod common;
 mod config;
+mod downloads;
 mod external;
+mod quota;
+mod retention;
+mod storage;
 mod submissions;
 mod uploads;
 
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::tracker::PodTracker;
 use crate::external::s3::services::get_client;
-use axum::{routing::get, Router};
+use crate::submissions::events::services::EventHub;
+use axum::{routing::get, Extension, Router};
 use axum_keycloak_auth::{instance::KeycloakAuthInstance, instance::KeycloakConfig, Url};
 use config::Config;
 use migration::{Migrator, MigratorTrait};
@@ -46,6 +54,9 @@ async fn main() {
     ));
 
     let s3_client = get_client(&config).await;
+    let event_hub = Arc::new(EventHub::new());
+    let kube = Arc::new(KubeClientProvider::new());
+    let pod_tracker = Arc::new(PodTracker::new());
 
     // Set up your Axum app
     let app: Router = Router::new()
@@ -59,6 +70,7 @@ async fn main() {
                 db.clone(),
                 keycloak_auth_instance.clone(),
                 s3_client.clone(),
+                event_hub.clone(),
             ),
         )
         .nest(
@@ -69,10 +81,129 @@ async fn main() {
                 s3_client.clone(),
             ),
         )
+        .nest(
+            "/api/quota",
+            quota::views::router(
+                db.clone(),
+                keycloak_auth_instance.clone(),
+                s3_client.clone(),
+            ),
+        )
+        .nest(
+            "/api/retention",
+            retention::views::router(
+                db.clone(),
+                keycloak_auth_instance.clone(),
+                s3_client.clone(),
+            ),
+        )
+        .nest(
+            "/api/storage",
+            storage::views::router(
+                db.clone(),
+                keycloak_auth_instance.clone(),
+                s3_client.clone(),
+            ),
+        )
+        .nest(
+            "/api/admin",
+            admin::views::router(
+                db.clone(),
+                keycloak_auth_instance.clone(),
+                s3_client.clone(),
+            ),
+        )
+        .nest(
+            "/api/downloads",
+            downloads::views::router(s3_client.clone()),
+        )
         .nest(
             "/tus",
-            external::tus::views::router(db.clone(), keycloak_auth_instance, s3_client),
-        );
+            external::tus::views::router(
+                db.clone(),
+                keycloak_auth_instance,
+                s3_client.clone(),
+                event_hub,
+            ),
+        )
+        .layer(Extension(kube.clone()))
+        .layer(Extension(pod_tracker.clone()));
+
+    // Pods are watched once and shared, instead of listed on every request
+    {
+        let (pod_tracker, kube) = (pod_tracker.clone(), kube.clone());
+        tokio::spawn(async move { pod_tracker.run(kube).await });
+    }
+
+    // Queued runs are submitted to the cluster as capacity frees up
+    {
+        let (db, pod_tracker, kube) = (db.clone(), pod_tracker.clone(), kube.clone());
+        let interval = Duration::from_secs(config.dispatch_interval_seconds);
+        tokio::spawn(async move {
+            loop {
+                submissions::run_queue::services::dispatch(&db, &pod_tracker, &kube).await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Metrics are computed on their own, as reading the outputs of large
+    // runs would hold up the reconcilers
+    {
+        let (db, s3_client, pod_tracker) = (db.clone(), s3_client.clone(), pod_tracker.clone());
+        let interval = Duration::from_secs(config.interval_external_services);
+        tokio::spawn(async move {
+            loop {
+                submissions::run_metrics::services::compute_pending_metrics(
+                    &db,
+                    &s3_client,
+                    &pod_tracker,
+                )
+                .await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Deferred and recurring runs are queued once they are due
+    {
+        let db = db.clone();
+        let interval = Duration::from_secs(config.schedule_interval_seconds);
+        tokio::spawn(async move {
+            loop {
+                submissions::run_schedules::services::queue_due(&db).await;
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Apply the retention policy on its own, much longer, interval
+    if retention::models::RetentionPolicy::from_config(&config).has_rules() {
+        let (db, s3_client) = (db.clone(), s3_client.clone());
+        let interval = Duration::from_secs(config.retention_interval_hours * 60 * 60);
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = retention::services::run(&db, &s3_client, false).await {
+                    eprintln!("Retention policy error: {}", err);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    // Output sizes are listed from S3 periodically rather than per request
+    {
+        let (db, s3_client) = (db.clone(), s3_client.clone());
+        let interval = Duration::from_secs(config.storage_usage_interval_minutes * 60);
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = storage::services::refresh(&db, &s3_client).await {
+                    eprintln!("Storage usage refresh error: {}", err);
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
 
     let addr: std::net::SocketAddr = "0.0.0.0:3000".parse().unwrap();
     println!("Listening on {}", addr);
@@ -88,8 +219,18 @@ async fn main() {
             }
         }
         _ = tokio::spawn(async move {
+            let mut events_synced = submissions::run_events::services::LastSynced::new();
             loop {
-                crate::external::services::check_external_services().await;
+                crate::external::services::check_external_services(&pod_tracker).await;
+                crate::submissions::run_status::services::reconcile(&db, &pod_tracker, &kube).await;
+                crate::submissions::run_events::services::reconcile(
+                    &db,
+                    &pod_tracker,
+                    &kube,
+                    &mut events_synced,
+                )
+                .await;
+                crate::submissions::run_queue::services::record_outcomes(&db, &kube).await;
                 tokio::time::sleep(Duration::from_secs(config.interval_external_services)).await;
             }
         }) => {
@@ -0,0 +1,71 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct QuotaUsage {
+    pub used_bytes: i64,
+    pub quota_bytes: Option<i64>, // None if there is no quota
+    pub available_bytes: Option<i64>,
+}
+
+impl QuotaUsage {
+    pub fn new(used_bytes: i64, quota_bytes: Option<i64>) -> Self {
+        Self {
+            used_bytes,
+            quota_bytes,
+            available_bytes: quota_bytes.map(|quota| (quota - used_bytes).max(0)),
+        }
+    }
+
+    pub fn allows(&self, size_bytes: i64) -> bool {
+        match self.quota_bytes {
+            Some(quota) => self.used_bytes + size_bytes <= quota,
+            None => true,
+        }
+    }
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct Quota {
+    pub user: QuotaUsage,
+    pub project: QuotaUsage,
+}
+
+impl Quota {
+    pub fn allows(&self, size_bytes: i64) -> bool {
+        self.user.allows(size_bytes) && self.project.allows(size_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_up_to_the_quota() {
+        let usage = QuotaUsage::new(600, Some(1000));
+        assert!(usage.allows(400));
+        assert!(!usage.allows(401));
+    }
+
+    #[test]
+    fn allows_anything_without_a_quota() {
+        assert!(QuotaUsage::new(600, None).allows(i64::MAX - 600));
+    }
+
+    #[test]
+    fn reports_no_available_bytes_once_exceeded() {
+        assert_eq!(QuotaUsage::new(1200, Some(1000)).available_bytes, Some(0));
+        assert_eq!(QuotaUsage::new(200, Some(1000)).available_bytes, Some(800));
+    }
+
+    #[test]
+    fn requires_both_quotas() {
+        let quota = Quota {
+            user: QuotaUsage::new(0, Some(100)),
+            project: QuotaUsage::new(950, Some(1000)),
+        };
+        assert!(quota.allows(50));
+        assert!(!quota.allows(51));
+    }
+}
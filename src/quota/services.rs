@@ -0,0 +1,87 @@
+use super::models::{Quota, QuotaUsage};
+use crate::config::Config;
+use crate::storage::db as StorageDB;
+use crate::submissions::db as SubmissionDB;
+use crate::uploads::partials::db as PartialDB;
+use anyhow::Result;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, JoinType, QueryFilter, QuerySelect,
+    RelationTrait, SelectColumns,
+};
+use sea_query::{Alias, Expr, Func};
+
+async fn get_used_bytes(db: &DatabaseConnection, subject: Option<&str>) -> Result<i64> {
+    // Inputs, and outputs including archived ones, of the submissions created
+    // by the user or of the whole deployment. Output sizes come from the
+    // storage usage cache rather than listing the bucket on every upload.
+    let mut query = StorageDB::Entity::find();
+    if let Some(subject) = subject {
+        query = query.filter(StorageDB::Column::CreatedBy.eq(subject));
+    }
+    let used_bytes: Option<i64> = query
+        .select_only()
+        .select_column_as(
+            Expr::expr(Func::coalesce([
+                Func::sum(Expr::col(StorageDB::Column::TotalBytes)).into(),
+                Expr::val(0).into(),
+            ]))
+            .cast_as(Alias::new("BIGINT")),
+            "used_bytes",
+        )
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    // Partial uploads not yet concatenated into a final upload, counting
+    // what was received of those whose length is still deferred
+    let mut partials = PartialDB::Entity::find().filter(PartialDB::Column::FinalObjectId.is_null());
+    if let Some(subject) = subject {
+        partials = partials
+            .join(JoinType::InnerJoin, PartialDB::Relation::Submissions.def())
+            .filter(SubmissionDB::Column::CreatedBy.eq(subject));
+    }
+    let partial_bytes: Option<i64> = partials
+        .select_only()
+        .select_column_as(
+            Expr::expr(Func::coalesce([
+                Func::sum(
+                    Func::cust(Alias::new("GREATEST"))
+                        .arg(Expr::col((PartialDB::Entity, PartialDB::Column::SizeBytes)))
+                        .arg(Expr::col((
+                            PartialDB::Entity,
+                            PartialDB::Column::BytesReceived,
+                        ))),
+                )
+                .into(),
+                Expr::val(0).into(),
+            ]))
+            .cast_as(Alias::new("BIGINT")),
+            "used_bytes",
+        )
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    Ok(used_bytes.unwrap_or(0) + partial_bytes.unwrap_or(0))
+}
+
+pub async fn get_project_quota(db: &DatabaseConnection) -> Result<QuotaUsage> {
+    let config = Config::from_env();
+
+    Ok(QuotaUsage::new(
+        get_used_bytes(db, None).await?,
+        config.quota_project_bytes,
+    ))
+}
+
+pub async fn get_quota(db: &DatabaseConnection, subject: &str) -> Result<Quota> {
+    let config = Config::from_env();
+
+    Ok(Quota {
+        user: QuotaUsage::new(
+            get_used_bytes(db, Some(subject)).await?,
+            config.quota_user_bytes,
+        ),
+        project: get_project_quota(db).await?,
+    })
+}
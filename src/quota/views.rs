@@ -0,0 +1,46 @@
+use crate::common::auth::Role;
+use aws_sdk_s3::Client as S3Client;
+use axum::{extract::State, http::StatusCode, routing, Extension, Json, Router};
+use axum_keycloak_auth::{
+    decode::KeycloakToken, instance::KeycloakAuthInstance, layer::KeycloakAuthLayer,
+    PassthroughMode,
+};
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+
+pub fn router(
+    db: DatabaseConnection,
+    keycloak_auth_instance: Arc<KeycloakAuthInstance>,
+    s3: Arc<S3Client>,
+) -> Router {
+    Router::new()
+        .route("/", routing::get(get_quota))
+        .with_state((db, s3))
+        // Any authenticated user can see their own usage
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance)
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .build(),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/quota",
+    responses((status = OK, body = super::models::Quota))
+)]
+pub async fn get_quota(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+) -> Result<Json<super::models::Quota>, (StatusCode, Json<String>)> {
+    match super::services::get_quota(&db, &token.subject).await {
+        Ok(quota) => Ok(Json(quota)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to calculate storage usage".to_string()),
+        )),
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::Config;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RetentionPolicy {
+    pub input_days: Option<i64>,
+    pub archive_days: Option<i64>,
+    pub archive_storage_class: Option<String>,
+    pub output_days: Option<i64>,
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            input_days: config.retention_input_days,
+            archive_days: config.retention_archive_days,
+            archive_storage_class: config.retention_archive_storage_class.clone(),
+            output_days: config.retention_output_days,
+        }
+    }
+
+    pub fn has_rules(&self) -> bool {
+        self.input_days.is_some() || self.archive_days.is_some() || self.output_days.is_some()
+    }
+}
+
+#[derive(ToSchema, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionActionKind {
+    DeleteInput,
+    ArchiveOutput,
+    DeleteOutput,
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RetentionAction {
+    pub submission_id: Uuid,
+    pub kind: RetentionActionKind,
+    pub path: String,
+    pub size_bytes: i64,
+    pub error: Option<String>, // Set if applying the action failed
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RetentionError {
+    pub submission_id: Uuid,
+    pub error: String, // Why the submission could not be evaluated
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub policy: RetentionPolicy,
+    pub actions: Vec<RetentionAction>,
+    pub bytes_deleted: i64,
+    pub bytes_archived: i64,
+    pub errors: Vec<RetentionError>,
+    pub generated_on: NaiveDateTime,
+}
@@ -0,0 +1,226 @@
+use super::models::{
+    RetentionAction, RetentionActionKind, RetentionError, RetentionPolicy, RetentionReport,
+};
+use crate::config::Config;
+use crate::external::s3::models::{OutputObject, OutputObjectResponse};
+use anyhow::{anyhow, Result};
+use aws_sdk_s3::Client as S3Client;
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, PaginatorTrait, QueryFilter,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+enum Target {
+    Input(Uuid),
+    Output(OutputObject),
+}
+
+fn is_older_than(time: DateTime<Utc>, days: Option<i64>, now: DateTime<Utc>) -> bool {
+    days.is_some_and(|days| time + Duration::days(days) <= now)
+}
+
+async fn last_successful_run_finished(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+) -> Result<Option<DateTime<Utc>>> {
+    // When the latest successful run finished, from its queue entry, or the
+    // last update of its status for runs from before the queue
+    let queued = crate::submissions::run_queue::db::Entity::find()
+        .filter(crate::submissions::run_queue::db::Column::SubmissionId.eq(submission_id))
+        .filter(crate::submissions::run_queue::db::Column::Outcome.eq("Succeeded"))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|queued| queued.finished_on);
+    let run_status = crate::submissions::run_status::db::Entity::find()
+        .filter(crate::submissions::run_status::db::Column::SubmissionId.eq(submission_id))
+        .filter(crate::submissions::run_status::db::Column::IsSuccessful.eq(true))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|run_status| run_status.last_updated);
+
+    Ok(queued.chain(run_status).max().map(|time| time.and_utc()))
+}
+
+async fn has_pending_runs(db: &DatabaseConnection, submission_id: Uuid) -> Result<bool> {
+    // Runs waiting in the queue, in the cluster, or scheduled to be queued
+    // still need the inputs, however long ago an earlier run succeeded
+    let queued = crate::submissions::run_queue::db::Entity::find()
+        .filter(crate::submissions::run_queue::db::Column::SubmissionId.eq(submission_id))
+        .filter(crate::submissions::run_queue::db::Column::Status.is_in([
+            crate::submissions::run_queue::db::QueueStatus::Queued,
+            crate::submissions::run_queue::db::QueueStatus::Dispatched,
+        ]))
+        .filter(crate::submissions::run_queue::db::Column::Outcome.is_null())
+        .count(db)
+        .await?;
+    let scheduled = crate::submissions::run_schedules::db::Entity::find()
+        .filter(crate::submissions::run_schedules::db::Column::SubmissionId.eq(submission_id))
+        .count(db)
+        .await?;
+    let running = crate::submissions::run_status::db::Entity::find()
+        .filter(crate::submissions::run_status::db::Column::SubmissionId.eq(submission_id))
+        .filter(crate::submissions::run_status::db::Column::IsRunning.eq(true))
+        .count(db)
+        .await?;
+
+    Ok(queued + scheduled + running > 0)
+}
+
+async fn plan_submission(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    policy: &RetentionPolicy,
+    submission: &crate::submissions::db::Model,
+    now: DateTime<Utc>,
+) -> Result<Vec<(RetentionAction, Target)>> {
+    let (outputs, _) =
+        crate::external::s3::services::list_outputs(s3, submission.id, None, None).await?;
+    let archived = crate::external::s3::services::list_archived_outputs(s3, submission.id).await?;
+    let action = |kind, path: String, size_bytes| RetentionAction {
+        submission_id: submission.id,
+        kind,
+        path,
+        size_bytes,
+        error: None,
+    };
+    let mut actions = vec![];
+
+    // Inputs are only removed a while after a run has succeeded, so a failed
+    // run's partial outputs never cost the raw data
+    let run_succeeded = last_successful_run_finished(db, submission.id).await?;
+    if run_succeeded.is_some_and(|time| is_older_than(time, policy.input_days, now))
+        && !has_pending_runs(db, submission.id).await?
+    {
+        let inputs = submission
+            .find_related(crate::uploads::db::Entity)
+            .all(db)
+            .await?;
+        for input in inputs {
+            actions.push((
+                action(
+                    RetentionActionKind::DeleteInput,
+                    input.relative_path.clone(),
+                    input.size_bytes,
+                ),
+                Target::Input(input.id),
+            ));
+        }
+    }
+
+    // Deleting takes precedence over archiving for outputs old enough for both
+    for output in outputs {
+        let kind = if is_older_than(output.last_modified, policy.output_days, now) {
+            RetentionActionKind::DeleteOutput
+        } else if is_older_than(output.last_modified, policy.archive_days, now) {
+            RetentionActionKind::ArchiveOutput
+        } else {
+            continue;
+        };
+        let path = OutputObjectResponse::from(output.clone()).path;
+        actions.push((
+            action(kind, path, output.size_bytes),
+            Target::Output(output),
+        ));
+    }
+    for output in archived {
+        if is_older_than(output.last_modified, policy.output_days, now) {
+            let path = OutputObjectResponse::from(output.clone()).path;
+            actions.push((
+                action(RetentionActionKind::DeleteOutput, path, output.size_bytes),
+                Target::Output(output),
+            ));
+        }
+    }
+
+    Ok(actions)
+}
+
+async fn apply(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    policy: &RetentionPolicy,
+    kind: RetentionActionKind,
+    target: Target,
+) -> Result<()> {
+    match (kind, target) {
+        (RetentionActionKind::DeleteInput, Target::Input(id)) => {
+            crate::uploads::services::delete_object(db, s3, id).await
+        }
+        (RetentionActionKind::ArchiveOutput, Target::Output(output)) => {
+            crate::external::s3::services::archive_output_object(
+                s3,
+                &output,
+                policy.archive_storage_class.as_deref(),
+            )
+            .await
+        }
+        (RetentionActionKind::DeleteOutput, Target::Output(output)) => {
+            crate::external::s3::services::delete_output_object(s3, output)
+                .await
+                .map_err(|e| anyhow!(e.to_string()))
+        }
+        _ => Err(anyhow!("Action does not apply to this object")),
+    }
+}
+
+pub async fn run(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    dry_run: bool,
+) -> Result<RetentionReport> {
+    // Evaluates the policy over all submissions that are not pinned, and
+    // applies the resulting actions unless this is a dry run
+    let policy = RetentionPolicy::from_config(&Config::from_env());
+    let now = Utc::now();
+    let mut report = RetentionReport {
+        dry_run,
+        policy,
+        actions: vec![],
+        bytes_deleted: 0,
+        bytes_archived: 0,
+        errors: vec![],
+        generated_on: now.naive_utc(),
+    };
+    if !report.policy.has_rules() {
+        return Ok(report);
+    }
+
+    let submissions = crate::submissions::db::Entity::find()
+        .filter(crate::submissions::db::Column::IsPinned.eq(false))
+        .all(db)
+        .await?;
+
+    for submission in submissions {
+        // A submission that cannot be evaluated does not hold up the others
+        let actions = match plan_submission(db, s3, &report.policy, &submission, now).await {
+            Ok(actions) => actions,
+            Err(e) => {
+                report.errors.push(RetentionError {
+                    submission_id: submission.id,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        for (mut action, target) in actions {
+            if !dry_run {
+                if let Err(e) = apply(db, s3, &report.policy, action.kind, target).await {
+                    action.error = Some(e.to_string());
+                    report.actions.push(action);
+                    continue;
+                }
+            }
+            match action.kind {
+                RetentionActionKind::ArchiveOutput => report.bytes_archived += action.size_bytes,
+                _ => report.bytes_deleted += action.size_bytes,
+            }
+            report.actions.push(action);
+        }
+    }
+
+    Ok(report)
+}
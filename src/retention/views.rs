@@ -0,0 +1,45 @@
+use crate::common::auth::Role;
+use aws_sdk_s3::Client as S3Client;
+use axum::{extract::State, http::StatusCode, routing, Json, Router};
+use axum_keycloak_auth::{
+    instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
+};
+use sea_orm::DatabaseConnection;
+use std::sync::Arc;
+
+pub fn router(
+    db: DatabaseConnection,
+    keycloak_auth_instance: Arc<KeycloakAuthInstance>,
+    s3: Arc<S3Client>,
+) -> Router {
+    Router::new()
+        .route("/report", routing::get(get_report))
+        .with_state((db, s3))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance)
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .required_roles(vec![Role::Administrator])
+                .build(),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/retention/report",
+    responses((status = OK, body = super::models::RetentionReport))
+)]
+pub async fn get_report(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+) -> Result<Json<super::models::RetentionReport>, (StatusCode, Json<String>)> {
+    // Dry run of the retention policy: what the next scheduled run would do
+    match super::services::run(&db, &s3, true).await {
+        Ok(report) => Ok(Json(report)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to evaluate the retention policy".to_string()),
+        )),
+    }
+}
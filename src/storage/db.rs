@@ -0,0 +1,25 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+// Read-only view combining input sizes with the cached output sizes
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "submission_storage_usage")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub submission_id: Uuid,
+    pub name: String,
+    pub created_by: Option<String>,
+    pub input_bytes: i64,
+    pub input_count: i64,
+    pub output_bytes: i64,
+    pub output_count: i64,
+    pub archived_bytes: i64,
+    pub total_bytes: i64,
+    pub outputs_last_updated: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
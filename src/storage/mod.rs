@@ -0,0 +1,6 @@
+pub mod db;
+pub mod models;
+pub mod orphans;
+pub mod output_usage;
+pub mod services;
+pub mod views;
@@ -0,0 +1,79 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct SubmissionUsage {
+    pub submission_id: Uuid,
+    pub name: String,
+    pub created_by: Option<String>,
+    pub input_bytes: i64,
+    pub input_count: i64,
+    pub output_bytes: i64,
+    pub output_count: i64,
+    pub archived_bytes: i64,
+    pub total_bytes: i64,
+    pub outputs_last_updated: Option<NaiveDateTime>,
+}
+
+impl From<super::db::Model> for SubmissionUsage {
+    fn from(model: super::db::Model) -> Self {
+        Self {
+            submission_id: model.submission_id,
+            name: model.name,
+            created_by: model.created_by,
+            input_bytes: model.input_bytes,
+            input_count: model.input_count,
+            output_bytes: model.output_bytes,
+            output_count: model.output_count,
+            archived_bytes: model.archived_bytes,
+            total_bytes: model.total_bytes,
+            outputs_last_updated: model.outputs_last_updated,
+        }
+    }
+}
+
+#[derive(ToSchema, Serialize, Debug, Default)]
+pub struct OwnerUsage {
+    pub created_by: Option<String>,
+    pub submission_count: i64,
+    pub input_bytes: i64,
+    pub output_bytes: i64,
+    pub archived_bytes: i64,
+    pub total_bytes: i64,
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct OrphanedObject {
+    pub key: String,
+    pub size_bytes: i64,
+    pub last_modified: Option<NaiveDateTime>,
+}
+
+impl From<super::orphans::db::Model> for OrphanedObject {
+    fn from(model: super::orphans::db::Model) -> Self {
+        Self {
+            key: model.key,
+            size_bytes: model.size_bytes,
+            last_modified: model.last_modified,
+        }
+    }
+}
+
+#[derive(ToSchema, Serialize, Debug, Default)]
+pub struct UsageTotals {
+    pub input_bytes: i64,
+    pub output_bytes: i64,
+    pub archived_bytes: i64,
+    pub orphaned_bytes: i64,
+    pub total_bytes: i64,
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct StorageUsage {
+    pub submissions: Vec<SubmissionUsage>,
+    pub owners: Vec<OwnerUsage>,
+    pub orphaned_objects: Vec<OrphanedObject>,
+    pub totals: UsageTotals,
+}
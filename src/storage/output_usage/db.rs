@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "output_storage_usage")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub submission_id: Uuid,
+    pub output_bytes: i64,
+    pub output_count: i64,
+    pub archived_bytes: i64,
+    pub last_updated: NaiveDateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
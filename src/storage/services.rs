@@ -0,0 +1,147 @@
+use crate::config::Config;
+use anyhow::Result;
+use aws_sdk_s3::Client as S3Client;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::Utc;
+use sea_orm::{DatabaseConnection, EntityTrait, QuerySelect, Set, TransactionTrait};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Default)]
+struct OutputTally {
+    output_bytes: i64,
+    output_count: i64,
+    archived_bytes: i64,
+}
+
+pub enum ObjectOwner {
+    Output(Uuid),
+    Archived(Uuid),
+    Upload(Uuid),
+    Unknown,
+}
+
+pub fn classify(key: &str, prefix: &str) -> ObjectOwner {
+    // Keys are {prefix}/outputs/{submission_id}/..., the archived equivalent
+    // under {prefix}/archive/, or {prefix}/{upload_id} for uploads (tusd adds
+    // a {upload_id}.info alongside, and .part files while uploading)
+    let Some(path) = key.strip_prefix(prefix) else {
+        return ObjectOwner::Unknown;
+    };
+    let submission_id = |path: &str| {
+        path.split('/')
+            .next()
+            .and_then(|id| Uuid::parse_str(id).ok())
+    };
+    if let Some(path) = path.strip_prefix("outputs/") {
+        return submission_id(path).map_or(ObjectOwner::Unknown, ObjectOwner::Output);
+    }
+    if let Some(path) = path.strip_prefix("archive/outputs/") {
+        return submission_id(path).map_or(ObjectOwner::Unknown, ObjectOwner::Archived);
+    }
+    let id = path.split('.').next().unwrap_or_default();
+    match Uuid::parse_str(id) {
+        Ok(id) if !path.contains('/') => ObjectOwner::Upload(id),
+        _ => ObjectOwner::Unknown,
+    }
+}
+
+pub async fn refresh(db: &DatabaseConnection, s3: &Arc<S3Client>) -> Result<()> {
+    // List everything under the deployment's prefix once, attributing each
+    // object to a submission or upload, and cache the results
+    let config = Config::from_env();
+    let prefix = format!("{}/", config.s3_prefix);
+    let submission_ids: HashSet<Uuid> = crate::submissions::db::Entity::find()
+        .select_only()
+        .column(crate::submissions::db::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    let mut upload_ids: HashSet<Uuid> = crate::uploads::db::Entity::find()
+        .select_only()
+        .column(crate::uploads::db::Column::Id)
+        .into_tuple()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    upload_ids.extend(
+        crate::uploads::partials::db::Entity::find()
+            .select_only()
+            .column(crate::uploads::partials::db::Column::Id)
+            .into_tuple::<Uuid>()
+            .all(db)
+            .await?,
+    );
+
+    let now = Utc::now().naive_utc();
+    let mut tallies: HashMap<Uuid, OutputTally> = HashMap::new();
+    let mut orphans: Vec<super::orphans::db::ActiveModel> = vec![];
+    let mut pages = s3
+        .list_objects_v2()
+        .bucket(&config.s3_bucket)
+        .prefix(&prefix)
+        .into_paginator()
+        .send();
+
+    while let Some(page) = pages.next().await {
+        for object in page?.contents() {
+            let key = object.key().unwrap_or_default();
+            let size_bytes = object.size.unwrap_or(0);
+            match classify(key, &prefix) {
+                ObjectOwner::Output(id) if submission_ids.contains(&id) => {
+                    let tally = tallies.entry(id).or_default();
+                    tally.output_bytes += size_bytes;
+                    tally.output_count += 1;
+                }
+                ObjectOwner::Archived(id) if submission_ids.contains(&id) => {
+                    tallies.entry(id).or_default().archived_bytes += size_bytes;
+                }
+                ObjectOwner::Upload(id) if upload_ids.contains(&id) => {}
+                _ => orphans.push(super::orphans::db::ActiveModel {
+                    key: Set(key.to_string()),
+                    size_bytes: Set(size_bytes),
+                    last_modified: Set(object
+                        .last_modified
+                        .and_then(|time| time.to_chrono_utc().ok())
+                        .map(|time| time.naive_utc())),
+                    found_on: Set(now),
+                }),
+            }
+        }
+    }
+
+    let usage: Vec<super::output_usage::db::ActiveModel> = tallies
+        .into_iter()
+        .map(|(id, tally)| super::output_usage::db::ActiveModel {
+            submission_id: Set(id),
+            output_bytes: Set(tally.output_bytes),
+            output_count: Set(tally.output_count),
+            archived_bytes: Set(tally.archived_bytes),
+            last_updated: Set(now),
+        })
+        .collect();
+
+    // Replace the cache in one go so readers never see a partial refresh
+    let txn = db.begin().await?;
+    super::output_usage::db::Entity::delete_many()
+        .exec(&txn)
+        .await?;
+    super::orphans::db::Entity::delete_many().exec(&txn).await?;
+    for chunk in usage.chunks(1000) {
+        super::output_usage::db::Entity::insert_many(chunk.to_vec())
+            .exec(&txn)
+            .await?;
+    }
+    for chunk in orphans.chunks(1000) {
+        super::orphans::db::Entity::insert_many(chunk.to_vec())
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use crate::common::auth::Role;
+use crate::common::filter::{apply_filters, parse_range};
+use crate::common::models::FilterOptions;
+use crate::common::pagination::calculate_content_range;
+use crate::common::sort::generic_sort;
+use aws_sdk_s3::Client as S3Client;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing, Json, Router,
+};
+use axum_keycloak_auth::{
+    instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
+};
+use sea_orm::{query::*, DatabaseConnection, EntityTrait};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const RESOURCE_NAME: &str = "storage";
+
+pub fn router(
+    db: DatabaseConnection,
+    keycloak_auth_instance: Arc<KeycloakAuthInstance>,
+    s3: Arc<S3Client>,
+) -> Router {
+    Router::new()
+        .route("/usage", routing::get(get_usage))
+        .with_state((db, s3))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance)
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .required_roles(vec![Role::Administrator])
+                .build(),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/usage", RESOURCE_NAME),
+    responses((status = OK, body = super::models::StorageUsage))
+)]
+pub async fn get_usage(
+    Query(params): Query<FilterOptions>,
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<String>)> {
+    // The submissions are paginated and sortable, the owners and totals are
+    // over all submissions
+    let (offset, limit) = parse_range(params.range.clone());
+
+    let condition = apply_filters(
+        params.filter.clone(),
+        &[
+            ("name", super::db::Column::Name),
+            ("created_by", super::db::Column::CreatedBy),
+        ],
+    );
+
+    let (order_column, order_direction) = generic_sort(
+        params.sort.clone(),
+        &[
+            ("submission_id", super::db::Column::SubmissionId),
+            ("name", super::db::Column::Name),
+            ("created_by", super::db::Column::CreatedBy),
+            ("input_bytes", super::db::Column::InputBytes),
+            ("input_count", super::db::Column::InputCount),
+            ("output_bytes", super::db::Column::OutputBytes),
+            ("output_count", super::db::Column::OutputCount),
+            ("archived_bytes", super::db::Column::ArchivedBytes),
+            ("total_bytes", super::db::Column::TotalBytes),
+            (
+                "outputs_last_updated",
+                super::db::Column::OutputsLastUpdated,
+            ),
+        ],
+        super::db::Column::SubmissionId,
+    );
+
+    let server_error = |_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to fetch storage usage".to_string()),
+        )
+    };
+
+    let submissions: Vec<super::models::SubmissionUsage> = super::db::Entity::find()
+        .filter(condition.clone())
+        .order_by(order_column, order_direction)
+        .offset(offset)
+        .limit(limit)
+        .all(&db)
+        .await
+        .map_err(server_error)?
+        .into_iter()
+        .map(|obj| obj.into())
+        .collect();
+
+    let all_usage = super::db::Entity::find()
+        .all(&db)
+        .await
+        .map_err(server_error)?;
+    let total_count = super::db::Entity::find()
+        .filter(condition)
+        .count(&db)
+        .await
+        .unwrap_or(0);
+
+    let orphaned_objects: Vec<super::models::OrphanedObject> = super::orphans::db::Entity::find()
+        .all(&db)
+        .await
+        .map_err(server_error)?
+        .into_iter()
+        .map(|obj| obj.into())
+        .collect();
+
+    let mut owners: BTreeMap<Option<String>, super::models::OwnerUsage> = BTreeMap::new();
+    let mut totals = super::models::UsageTotals::default();
+    for usage in all_usage {
+        let owner =
+            owners
+                .entry(usage.created_by.clone())
+                .or_insert_with(|| super::models::OwnerUsage {
+                    created_by: usage.created_by.clone(),
+                    ..Default::default()
+                });
+        owner.submission_count += 1;
+        owner.input_bytes += usage.input_bytes;
+        owner.output_bytes += usage.output_bytes;
+        owner.archived_bytes += usage.archived_bytes;
+        owner.total_bytes += usage.total_bytes;
+
+        totals.input_bytes += usage.input_bytes;
+        totals.output_bytes += usage.output_bytes;
+        totals.archived_bytes += usage.archived_bytes;
+        totals.total_bytes += usage.total_bytes;
+    }
+    totals.orphaned_bytes = orphaned_objects.iter().map(|obj| obj.size_bytes).sum();
+    totals.total_bytes += totals.orphaned_bytes;
+
+    // Largest owners first
+    let mut owners: Vec<super::models::OwnerUsage> = owners.into_values().collect();
+    owners.sort_by_key(|owner| std::cmp::Reverse(owner.total_bytes));
+
+    let headers = calculate_content_range(offset, limit, total_count, RESOURCE_NAME);
+
+    Ok((
+        headers,
+        Json(super::models::StorageUsage {
+            submissions,
+            owners,
+            orphaned_objects,
+            totals,
+        }),
+    ))
+}
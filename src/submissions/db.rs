@@ -16,6 +16,12 @@ pub struct Model {
     pub comment: Option<String>,
     pub created_on: NaiveDateTime,
     pub last_updated: NaiveDateTime,
+    pub created_by: Option<String>,
+    pub is_pinned: bool,             // Exempt from the retention policy
+    pub last_run_id: i32,            // Run ids are allocated from this counter
+    pub retry_max_attempts: i32,     // One attempt means failed runs are not retried
+    pub retry_backoff_seconds: i32,  // Doubled after each attempt
+    pub retry_reasons: Option<Json>, // Retryable failure reasons, the defaults if unset
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -24,6 +30,10 @@ pub enum Relation {
     FileObjectAssociations,
     #[sea_orm(has_many = "crate::submissions::run_status::db::Entity")]
     RunStatus,
+    #[sea_orm(has_many = "crate::submissions::run_metrics::db::Entity")]
+    RunMetrics,
+    #[sea_orm(has_many = "crate::submissions::run_queue::db::Entity")]
+    RunQueue,
 }
 
 impl Related<crate::uploads::db::Entity> for Entity {
@@ -47,4 +57,16 @@ impl Related<crate::submissions::run_status::db::Entity> for Entity {
     }
 }
 
+impl Related<crate::submissions::run_metrics::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RunMetrics.def()
+    }
+}
+
+impl Related<crate::submissions::run_queue::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RunQueue.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,96 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadState {
+    Created,
+    Receiving,
+    Completed,
+    Terminated,
+}
+
+#[derive(ToSchema, Serialize, Clone, Debug)]
+pub struct UploadEvent {
+    pub submission_id: Uuid,
+    pub upload_id: Uuid,
+    pub is_partial: bool,
+    pub state: UploadState,
+    pub bytes_received: i64,
+    pub size_bytes: i64,
+    pub processing_message: Option<String>,
+    pub time_utc: NaiveDateTime,
+}
+
+impl UploadEvent {
+    pub fn from_upload(
+        submission_id: Uuid,
+        upload: &crate::uploads::db::Model,
+        state: UploadState,
+    ) -> Self {
+        Self {
+            submission_id,
+            upload_id: upload.id,
+            is_partial: false,
+            state,
+            bytes_received: upload.bytes_received,
+            size_bytes: upload.size_bytes,
+            processing_message: upload.processing_message.clone(),
+            time_utc: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn from_partial(partial: &crate::uploads::partials::db::Model, state: UploadState) -> Self {
+        Self {
+            submission_id: partial.submission_id,
+            upload_id: partial.id,
+            is_partial: true,
+            state,
+            bytes_received: partial.bytes_received,
+            size_bytes: partial.size_bytes,
+            processing_message: None,
+            time_utc: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+#[derive(ToSchema, Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SubmissionEvent {
+    Upload(UploadEvent),
+}
+
+impl SubmissionEvent {
+    pub fn submission_id(&self) -> Uuid {
+        match self {
+            SubmissionEvent::Upload(event) => event.submission_id,
+        }
+    }
+
+    // Name of the server-sent event, so clients can listen per type
+    pub fn name(&self) -> &'static str {
+        match self {
+            SubmissionEvent::Upload(_) => "upload",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TicketClaims {
+    pub submission_id: Uuid,
+    pub purpose: String, // Always TICKET_PURPOSE, so no other token is accepted
+    pub exp: usize,
+}
+
+#[derive(ToSchema, Serialize)]
+pub struct EventsTicket {
+    pub ticket: String, // Passed as ?ticket= as EventSource cannot set headers
+    pub expires_in_seconds: i64,
+}
+
+#[derive(Deserialize)]
+pub struct EventsOptions {
+    pub ticket: String,
+}
@@ -0,0 +1,74 @@
+use super::models::{SubmissionEvent, TicketClaims};
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+// Events are dropped for subscribers that fall this far behind
+const EVENT_HUB_CAPACITY: usize = 1024;
+
+// Tickets only need to last until the EventSource connects, a reconnecting
+// client asks for a new one
+pub const TICKET_EXPIRY_SECONDS: i64 = 60;
+const TICKET_PURPOSE: &str = "submission-events";
+
+pub struct EventHub {
+    sender: broadcast::Sender<SubmissionEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_HUB_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: SubmissionEvent) {
+        // Sending only fails when nobody is listening, which is fine
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SubmissionEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn create_ticket(submission_id: Uuid) -> Result<String> {
+    // Short-lived ticket for the events stream of one submission, so the
+    // Keycloak access token does not end up in URLs and access logs
+    let config = Config::from_env();
+    let expiry = chrono::Utc::now() + chrono::Duration::seconds(TICKET_EXPIRY_SECONDS);
+    let claims = TicketClaims {
+        submission_id,
+        purpose: TICKET_PURPOSE.to_string(),
+        exp: expiry.timestamp() as usize,
+    };
+
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.download_token_secret.as_bytes()),
+    )?)
+}
+
+pub fn validate_ticket(ticket: &str, submission_id: Uuid) -> Result<()> {
+    let config = Config::from_env();
+    let claims = decode::<TicketClaims>(
+        ticket,
+        &DecodingKey::from_secret(config.download_token_secret.as_bytes()),
+        &Validation::default(),
+    )?
+    .claims;
+
+    if claims.purpose != TICKET_PURPOSE || claims.submission_id != submission_id {
+        return Err(anyhow!("Ticket is not for the events of this submission"));
+    }
+
+    Ok(())
+}
@@ -1,5 +1,11 @@
 pub mod db;
+pub mod events;
 pub mod models;
+pub mod previews;
+pub mod run_events;
+pub mod run_metrics;
+pub mod run_queue;
+pub mod run_schedules;
 pub mod run_status;
 pub mod services;
 pub mod views;
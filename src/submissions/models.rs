@@ -14,14 +14,19 @@ pub struct Submission {
     comment: Option<String>,
     created_on: NaiveDateTime,
     last_updated: NaiveDateTime,
+    created_by: Option<String>,
+    is_pinned: bool,
+    retry_policy: super::run_queue::models::RetryPolicy,
     pub(super) associations: Vec<crate::uploads::models::UploadRead>,
     outputs: Vec<crate::external::s3::models::OutputObjectResponse>,
-    // status: Vec<super::run_status::models::RunStatus>,
+    pub(super) metrics: Vec<super::run_metrics::models::RunMetrics>,
+    pub(super) runs: Vec<super::run_status::models::RunStatus>, // Persisted by the reconciler
     status: Vec<crate::external::k8s::models::PodName>,
 }
 
 impl From<super::db::Model> for Submission {
     fn from(model: super::db::Model) -> Self {
+        let retry_policy = (&model).into();
         Self {
             id: model.id,
             name: model.name,
@@ -30,8 +35,13 @@ impl From<super::db::Model> for Submission {
             comment: model.comment,
             created_on: model.created_on,
             last_updated: model.last_updated,
+            created_by: model.created_by,
+            is_pinned: model.is_pinned,
+            retry_policy,
             associations: vec![],
             outputs: vec![],
+            metrics: vec![],
+            runs: vec![],
             status: vec![],
         }
     }
@@ -66,11 +76,9 @@ impl
 
         // Set the url for each output object
         for output in outputs.iter_mut() {
-            output.url = Some(format!(
-                "/api/submissions/{}/{}",
-                submission.id, output.filename
-            ));
+            output.url = Some(output_url(submission.id, &output.path));
         }
+        let retry_policy = (&submission).into();
         Self {
             id: submission.id,
             name: submission.name,
@@ -79,16 +87,31 @@ impl
             comment: submission.comment,
             created_on: submission.created_on,
             last_updated: submission.last_updated,
+            created_by: submission.created_by,
+            is_pinned: submission.is_pinned,
+            retry_policy,
             associations: uploads
                 .into_iter()
                 .map(|association| association.into())
                 .collect(),
             status: status.into_iter().map(|status| status.into()).collect(),
             outputs: outputs,
+            metrics: vec![],
+            runs: vec![],
         }
     }
 }
 
+pub fn output_url(submission_id: Uuid, path: &str) -> String {
+    format!("/api/submissions/{}/outputs/{}", submission_id, path)
+}
+
+#[derive(ToSchema, Deserialize, Default)]
+pub struct OutputListOptions {
+    pub prefix: Option<String>, // Relative path to list under, eg. "barcode01/"
+    pub delimiter: Option<String>, // Group deeper paths as directories, eg. "/"
+}
+
 #[derive(ToSchema, Deserialize, Serialize, DeriveIntoActiveModel)]
 pub struct SubmissionCreate {
     pub name: String,
@@ -109,8 +132,19 @@ pub struct SubmissionUpdate {
         with = "::serde_with::rust::double_option"
     )]
     pub comment: Option<Option<String>>,
+    #[serde(default)]
+    pub is_pinned: Option<bool>,
+    #[serde(default)]
+    pub retry_max_attempts: Option<i32>,
+    #[serde(default)]
+    pub retry_backoff_seconds: Option<i32>,
+    #[serde(default)]
+    pub retry_reasons: Option<Vec<String>>,
 }
 
+const MAX_RETRY_ATTEMPTS: i32 = 10;
+const MAX_RETRY_BACKOFF_SECONDS: i32 = 24 * 60 * 60;
+
 impl From<SubmissionUpdate> for ActiveModel {
     fn from(update: SubmissionUpdate) -> Self {
         // If the field is Some(None), update the field to None, if None,
@@ -132,10 +166,50 @@ impl From<SubmissionUpdate> for ActiveModel {
             processing_has_started: NotSet,
             processing_success: NotSet,
             created_on: NotSet,
+            created_by: NotSet,
+            is_pinned: match update.is_pinned {
+                Some(is_pinned) => Set(is_pinned),
+                _ => NotSet,
+            },
+            last_run_id: NotSet,
+            retry_max_attempts: match update.retry_max_attempts {
+                Some(max_attempts) => Set(max_attempts),
+                _ => NotSet,
+            },
+            retry_backoff_seconds: match update.retry_backoff_seconds {
+                Some(backoff_seconds) => Set(backoff_seconds),
+                _ => NotSet,
+            },
+            retry_reasons: match update.retry_reasons {
+                Some(reasons) => Set(Some(reasons.into())),
+                _ => NotSet,
+            },
         }
     }
 }
 impl SubmissionUpdate {
+    pub fn validate(&self) -> Result<(), String> {
+        // Bounded so the retry backoff of the last attempt stays within reach
+        if let Some(max_attempts) = self.retry_max_attempts {
+            if !(1..=MAX_RETRY_ATTEMPTS).contains(&max_attempts) {
+                return Err(format!(
+                    "retry_max_attempts must be between 1 and {}",
+                    MAX_RETRY_ATTEMPTS
+                ));
+            }
+        }
+        if let Some(backoff_seconds) = self.retry_backoff_seconds {
+            if !(0..=MAX_RETRY_BACKOFF_SECONDS).contains(&backoff_seconds) {
+                return Err(format!(
+                    "retry_backoff_seconds must be between 0 and {}",
+                    MAX_RETRY_BACKOFF_SECONDS
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn merge_into_activemodel(&self, mut model: ActiveModel) -> ActiveModel {
         // If the field is Some(None), update the field to None, if None,
         // do not update the field (double option)
@@ -151,19 +225,41 @@ impl SubmissionUpdate {
             Some(_) => Set(None),
             _ => NotSet,
         };
+        if let Some(is_pinned) = self.is_pinned {
+            model.is_pinned = Set(is_pinned);
+        }
+        if let Some(max_attempts) = self.retry_max_attempts {
+            model.retry_max_attempts = Set(max_attempts);
+        }
+        if let Some(backoff_seconds) = self.retry_backoff_seconds {
+            model.retry_backoff_seconds = Set(backoff_seconds);
+        }
+        if let Some(ref reasons) = self.retry_reasons {
+            model.retry_reasons = Set(Some(reasons.clone().into()));
+        }
         model.last_updated = Set(chrono::Utc::now().naive_utc());
 
         model
     }
 }
 
+#[derive(ToSchema, Deserialize, Default)]
+pub struct InputListOptions {
+    pub tree: Option<bool>, // Nest the inputs by their relative path
+}
+
+#[derive(ToSchema, Deserialize, Default)]
+pub struct ArchiveOptions {
+    pub glob: Option<String>, // Only include outputs matching, eg. *.fastq.gz
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DownloadPath {
     pub url: String,
 }
 #[derive(Debug, Serialize, Deserialize)]
-pub(super) struct Claims {
-    pub(super) submission_id: Uuid,
-    pub(super) filename: String,
-    pub(super) exp: usize,
+pub struct Claims {
+    pub submission_id: Uuid,
+    pub filename: String, // Path relative to the submission's outputs
+    pub exp: usize,
 }
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(ToSchema, Deserialize, Default)]
+pub struct PreviewOptions {
+    pub lines: Option<usize>, // Number of lines or rows to return
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputPreview {
+    Text {
+        lines: Vec<String>,
+        truncated: bool,
+    },
+    Table {
+        columns: Vec<String>,
+        rows: Vec<Vec<String>>,
+        truncated: bool,
+    },
+    Json {
+        #[schema(value_type = Object)]
+        content: serde_json::Value,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PreviewKind {
+    Text,
+    Table(char), // Delimiter
+    Json,
+}
+
+impl PreviewKind {
+    pub fn from_path(path: &str) -> Option<Self> {
+        // Decide on the preview from the extension, ignoring any .gz suffix
+        let filename = path.split('/').next_back().unwrap_or_default();
+        let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+        if filename.starts_with("sequencing_summary") {
+            // Guppy/Dorado summaries are tab-separated despite being .txt
+            return Some(PreviewKind::Table('\t'));
+        }
+        match filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()) {
+            Some(ext) => match ext.as_str() {
+                "tsv" => Some(PreviewKind::Table('\t')),
+                "csv" => Some(PreviewKind::Table(',')),
+                "json" => Some(PreviewKind::Json),
+                "txt" | "log" | "md" | "fastq" | "fq" | "fasta" | "fa" | "bed" | "vcf" => {
+                    Some(PreviewKind::Text)
+                }
+                _ => None,
+            },
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use super::models::{OutputPreview, PreviewKind};
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use async_compression::tokio::bufread::GzipDecoder;
+use aws_sdk_s3::Client as S3Client;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use uuid::Uuid;
+
+// At most this much of an object is read (and decompressed), however many
+// lines are requested, so previews stay cheap for multi-GB outputs
+const PREVIEW_MAX_BYTES: usize = 1024 * 1024;
+pub const PREVIEW_DEFAULT_LINES: usize = 50;
+pub const PREVIEW_MAX_LINES: usize = 1000;
+
+async fn read_capped(mut reader: impl AsyncRead + Unpin) -> (Vec<u8>, bool) {
+    // Read up to the cap. A range read of a gzip file ends mid-stream, so a
+    // decoding error after some data is treated as the end of the preview.
+    let mut buffer = vec![0; PREVIEW_MAX_BYTES];
+    let mut filled = 0;
+    while filled < PREVIEW_MAX_BYTES {
+        match reader.read(&mut buffer[filled..]).await {
+            Ok(0) => {
+                buffer.truncate(filled);
+                return (buffer, false);
+            }
+            Ok(n) => filled += n,
+            Err(_) => {
+                buffer.truncate(filled);
+                return (buffer, true);
+            }
+        }
+    }
+
+    (buffer, true)
+}
+
+async fn read_head(s3: &S3Client, key: &str, gzipped: bool) -> Result<(Vec<u8>, bool)> {
+    let config = Config::from_env();
+    let object = s3
+        .get_object()
+        .bucket(&config.s3_bucket)
+        .key(key)
+        .range(format!("bytes=0-{}", PREVIEW_MAX_BYTES - 1))
+        .send()
+        .await?;
+
+    // The object is larger than what was read if the range was cut short
+    let partial = object
+        .content_range()
+        .and_then(|range| range.rsplit_once('/'))
+        .and_then(|(_, total)| total.parse::<usize>().ok())
+        .is_some_and(|total| total > PREVIEW_MAX_BYTES);
+
+    let body = object.body.into_async_read();
+    let (bytes, truncated) = if gzipped {
+        read_capped(GzipDecoder::new(body)).await
+    } else {
+        read_capped(body).await
+    };
+
+    Ok((bytes, truncated || partial))
+}
+
+fn split_lines(bytes: &[u8], truncated: bool, max_lines: usize) -> (Vec<String>, bool) {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+    if truncated && !text.ends_with('\n') {
+        // The last line was cut by the byte cap
+        lines.pop();
+    }
+    let more_lines = lines.len() > max_lines;
+    lines.truncate(max_lines);
+
+    (lines, truncated || more_lines)
+}
+
+fn split_rows(
+    bytes: &[u8],
+    truncated: bool,
+    delimiter: char,
+    max_rows: usize,
+) -> (Vec<Vec<String>>, bool) {
+    // Quoted cells may hold the delimiter or span lines, so rows are parsed
+    // rather than split. Rows are allowed to differ in their number of cells.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter as u8)
+        .from_reader(bytes);
+    let mut rows: Vec<Vec<String>> = reader
+        .byte_records()
+        .map_while(|record| record.ok())
+        .map(|record| {
+            record
+                .iter()
+                .map(|cell| String::from_utf8_lossy(cell).into_owned())
+                .collect()
+        })
+        .collect();
+    if truncated && !bytes.ends_with(b"\n") {
+        // The last row was cut by the byte cap
+        rows.pop();
+    }
+    let more_rows = rows.len() > max_rows;
+    rows.truncate(max_rows);
+
+    (rows, truncated || more_rows)
+}
+
+pub async fn get_preview(
+    s3: &S3Client,
+    submission_id: Uuid,
+    path: &str,
+    kind: PreviewKind,
+    max_lines: usize,
+) -> Result<OutputPreview> {
+    let config = Config::from_env();
+    let key = format!("{}/outputs/{}/{}", config.s3_prefix, submission_id, path);
+    let (bytes, truncated) = read_head(s3, &key, path.ends_with(".gz")).await?;
+
+    match kind {
+        PreviewKind::Text => {
+            let (lines, truncated) = split_lines(&bytes, truncated, max_lines);
+            Ok(OutputPreview::Text { lines, truncated })
+        }
+        PreviewKind::Table(delimiter) => {
+            // The first line is the header, the rows follow it
+            let (rows, truncated) = split_rows(&bytes, truncated, delimiter, max_lines + 1);
+            let mut rows = rows.into_iter();
+            let columns = rows.next().unwrap_or_default();
+            Ok(OutputPreview::Table {
+                columns,
+                rows: rows.collect(),
+                truncated,
+            })
+        }
+        PreviewKind::Json => {
+            if truncated {
+                return Err(anyhow!("JSON file is too large to preview"));
+            }
+            Ok(OutputPreview::Json {
+                content: serde_json::from_slice(&bytes)?,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_cells() {
+        let (rows, truncated) = split_rows(b"id,name\n1,\"a,b\"\n2,\"c\nd\"\n", false, ',', 10);
+
+        assert_eq!(
+            rows,
+            [vec!["id", "name"], vec!["1", "a,b"], vec!["2", "c\nd"]]
+        );
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn allows_rows_of_different_lengths() {
+        let (rows, _) = split_rows(b"a\tb\tc\nd\n", false, '\t', 10);
+
+        assert_eq!(rows, [vec!["a", "b", "c"], vec!["d"]]);
+    }
+
+    #[test]
+    fn drops_the_row_cut_by_the_byte_cap() {
+        let (rows, truncated) = split_rows(b"a,b\nc,d\ne,", true, ',', 10);
+
+        assert_eq!(rows, [vec!["a", "b"], vec!["c", "d"]]);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn limits_the_number_of_rows() {
+        let (rows, truncated) = split_rows(b"1\n2\n3\n", false, ',', 2);
+
+        assert_eq!(rows, [vec!["1"], vec!["2"]]);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn drops_the_line_cut_by_the_byte_cap() {
+        let (lines, truncated) = split_lines(b"@read1\nACGT\n+\nII", true, 10);
+
+        assert_eq!(lines, ["@read1", "ACGT", "+"]);
+        assert!(truncated);
+    }
+}
@@ -0,0 +1,40 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "run_events")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub run_id: i64,
+    #[sea_orm(unique)]
+    pub event_uid: String, // UID of the Kubernetes event
+    pub involved_kind: String, // ie. Pod, TrainingWorkload
+    pub involved_name: String,
+    pub event_type: Option<String>, // Normal or Warning
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub count: i32,
+    pub first_seen: Option<NaiveDateTime>,
+    pub last_seen: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+}
+
+impl Related<crate::submissions::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submissions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
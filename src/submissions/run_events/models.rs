@@ -0,0 +1,30 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RunEvent {
+    pub involved_kind: String,
+    pub involved_name: String,
+    pub event_type: Option<String>,
+    pub reason: Option<String>, // ie. Scheduled, Pulling, Preempted, Evicted
+    pub message: Option<String>,
+    pub count: i32,
+    pub first_seen: Option<NaiveDateTime>,
+    pub last_seen: Option<NaiveDateTime>,
+}
+
+impl From<super::db::Model> for RunEvent {
+    fn from(model: super::db::Model) -> Self {
+        Self {
+            involved_kind: model.involved_kind,
+            involved_name: model.involved_name,
+            event_type: model.event_type,
+            reason: model.reason,
+            message: model.message,
+            count: model.count,
+            first_seen: model.first_seen,
+            last_seen: model.last_seen,
+        }
+    }
+}
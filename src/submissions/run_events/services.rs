@@ -0,0 +1,174 @@
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::services::{get_events_involving, get_pods, workload_name};
+use crate::external::k8s::tracker::PodTracker;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use k8s_openapi::api::core::v1::Event;
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Kubernetes keeps events for an hour by default
+const EVENT_TTL_SECONDS: u64 = 60 * 60;
+
+// When each run's events were last synced, and whether it had finished then
+pub type LastSynced = HashMap<(Uuid, i64), (Instant, bool)>;
+
+fn into_active_model(
+    submission_id: Uuid,
+    run_id: i64,
+    event: Event,
+) -> Option<super::db::ActiveModel> {
+    // Events without a UID cannot be deduplicated, so they are not kept
+    let event_uid = event.metadata.uid?;
+
+    // Recent events only set the event time, older ones the timestamps
+    let event_time: Option<NaiveDateTime> = event.event_time.map(|time| time.0.naive_utc());
+    let first_seen = event
+        .first_timestamp
+        .map(|time| time.0.naive_utc())
+        .or(event_time);
+    let last_seen = event
+        .last_timestamp
+        .map(|time| time.0.naive_utc())
+        .or(event_time)
+        .or(first_seen);
+
+    Some(super::db::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        submission_id: Set(submission_id),
+        run_id: Set(run_id),
+        event_uid: Set(event_uid),
+        involved_kind: Set(event.involved_object.kind.unwrap_or_default()),
+        involved_name: Set(event.involved_object.name.unwrap_or_default()),
+        event_type: Set(event.type_),
+        reason: Set(event.reason),
+        message: Set(event.message),
+        count: Set(event.count.unwrap_or(1)),
+        first_seen: Set(first_seen),
+        last_seen: Set(last_seen),
+    })
+}
+
+pub async fn sync_run_events(
+    db: &DatabaseConnection,
+    kube: &KubeClientProvider,
+    submission_id: Uuid,
+    run_id: i64,
+    pod_names: &BTreeSet<String>,
+) -> Result<()> {
+    // Events of the run's workload and of each of its pods
+    let mut object_names = vec![workload_name(submission_id, run_id)];
+    object_names.extend(pod_names.iter().cloned());
+
+    let models: Vec<super::db::ActiveModel> = get_events_involving(kube, &object_names)
+        .await?
+        .into_iter()
+        .filter_map(|event| into_active_model(submission_id, run_id, event))
+        .collect();
+    if models.is_empty() {
+        return Ok(());
+    }
+
+    // Repeated events are counted by Kubernetes on the same object
+    super::db::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::column(super::db::Column::EventUid)
+                .update_columns([
+                    super::db::Column::Message,
+                    super::db::Column::Count,
+                    super::db::Column::LastSeen,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn reconcile(
+    db: &DatabaseConnection,
+    tracker: &PodTracker,
+    kube: &KubeClientProvider,
+    last_synced: &mut LastSynced,
+) {
+    // Kubernetes expires events after an hour, so they are copied while the
+    // run's pods, or its workload, are still around. Finished runs are only
+    // synced once more after finishing, and then once per expiry period, as
+    // they hardly get new events
+    let pods = match get_pods(tracker) {
+        Ok(pods) => pods,
+        Err(_) => return,
+    };
+
+    let mut runs: BTreeMap<(Uuid, i64), (BTreeSet<String>, bool)> = BTreeMap::new();
+    for pod in pods {
+        let run = runs
+            .entry((pod.submission_id, pod.run_id as i64))
+            .or_insert_with(|| (BTreeSet::new(), true));
+        run.1 &= matches!(pod.latest_status.as_str(), "Succeeded" | "Failed");
+        run.0.insert(pod.name);
+    }
+
+    // Dispatched runs without pods yet, the workload's own events explain
+    // why none were created (ie. quota exceeded, admission denied)
+    match crate::submissions::run_queue::db::Entity::find()
+        .filter(
+            crate::submissions::run_queue::db::Column::Status
+                .eq(crate::submissions::run_queue::db::QueueStatus::Dispatched),
+        )
+        .filter(crate::submissions::run_queue::db::Column::Outcome.is_null())
+        .all(db)
+        .await
+    {
+        Ok(dispatched) => {
+            for queued in dispatched {
+                runs.entry((queued.submission_id, queued.run_id))
+                    .or_insert_with(|| (BTreeSet::new(), false));
+            }
+        }
+        Err(e) => println!("Failed to list dispatched runs for their events: {}", e),
+    }
+
+    // Pods may outlive their submission
+    let submission_ids: HashSet<Uuid> = runs
+        .keys()
+        .map(|(submission_id, _)| *submission_id)
+        .collect();
+    let existing_submissions: HashSet<Uuid> = match crate::submissions::db::Entity::find()
+        .filter(crate::submissions::db::Column::Id.is_in(submission_ids))
+        .all(db)
+        .await
+    {
+        Ok(submissions) => submissions.into_iter().map(|obj| obj.id).collect(),
+        Err(_) => return,
+    };
+
+    last_synced.retain(|run, _| runs.contains_key(run));
+    for (run, (pod_names, finished)) in runs
+        .iter()
+        .filter(|((submission_id, _), _)| existing_submissions.contains(submission_id))
+    {
+        let (submission_id, run_id) = *run;
+        let recently_synced = last_synced.get(run).is_some_and(|(synced, was_finished)| {
+            *was_finished && synced.elapsed() < Duration::from_secs(EVENT_TTL_SECONDS)
+        });
+        if *finished && recently_synced {
+            continue;
+        }
+
+        match sync_run_events(db, kube, submission_id, run_id, pod_names).await {
+            Ok(()) => {
+                last_synced.insert(*run, (Instant::now(), *finished));
+            }
+            Err(e) => println!(
+                "Failed to save events of submission {} run {}: {}",
+                submission_id, run_id, e
+            ),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "run_metrics")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub run_id: i64,
+    pub source: String, // Output the metrics were computed from, ie. "fastq (flat layout)"
+    pub read_count: i64,
+    pub total_bases: i64,
+    pub n50: i64,
+    pub mean_qscore: Option<f64>,
+    pub barcode_counts: Json,
+    pub time_added_utc: NaiveDateTime,
+    pub error: Option<String>, // Why the last computation failed, None once computed
+    pub attempts: i32,
+    pub next_attempt_on: Option<NaiveDateTime>, // None once computed or given up on
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+}
+
+impl Related<crate::submissions::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submissions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
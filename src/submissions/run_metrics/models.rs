@@ -0,0 +1,33 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use serde_json::Value;
+use utoipa::ToSchema;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct RunMetrics {
+    pub run_id: i64,
+    pub source: String,
+    pub read_count: i64,
+    pub total_bases: i64,
+    pub n50: i64,
+    pub mean_qscore: Option<f64>,
+    pub barcode_counts: Value,
+    pub time_added_utc: NaiveDateTime,
+    pub error: Option<String>, // Set if the metrics could not be computed
+}
+
+impl From<super::db::Model> for RunMetrics {
+    fn from(model: super::db::Model) -> Self {
+        Self {
+            run_id: model.run_id,
+            source: model.source,
+            read_count: model.read_count,
+            total_bases: model.total_bases,
+            n50: model.n50,
+            mean_qscore: model.mean_qscore,
+            barcode_counts: model.barcode_counts,
+            time_added_utc: model.time_added_utc,
+            error: model.error,
+        }
+    }
+}
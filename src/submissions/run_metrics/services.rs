@@ -0,0 +1,412 @@
+use crate::config::Config;
+use crate::external::k8s::tracker::PodTracker;
+use crate::external::s3::models::OutputObjectResponse;
+use anyhow::{anyhow, Result};
+use async_compression::tokio::bufread::GzipDecoder;
+use aws_sdk_s3::Client as S3Client;
+use chrono::{Duration, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter,
+    Set,
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader, Lines};
+use uuid::Uuid;
+
+// Failed computations are retried with an exponential backoff from this
+// delay, and given up on after the last attempt
+const RETRY_DELAY_MINUTES: i64 = 10;
+const MAX_ATTEMPTS: i32 = 5;
+
+#[derive(Default)]
+struct MetricsAccumulator {
+    read_count: i64,
+    total_bases: i64,
+    length_counts: BTreeMap<i64, i64>, // Read length -> count, for the N50
+    qscore_sum: f64,
+    qscore_count: i64,
+    barcode_counts: BTreeMap<String, i64>,
+}
+
+impl MetricsAccumulator {
+    fn add_read(&mut self, length: i64, qscore: Option<f64>, barcode: Option<&str>) {
+        self.read_count += 1;
+        self.total_bases += length;
+        *self.length_counts.entry(length).or_default() += 1;
+        if let Some(qscore) = qscore {
+            self.qscore_sum += qscore;
+            self.qscore_count += 1;
+        }
+        if let Some(barcode) = barcode {
+            *self.barcode_counts.entry(barcode.to_string()).or_default() += 1;
+        }
+    }
+
+    fn n50(&self) -> i64 {
+        // Length of the read at which half of all bases are in reads at least
+        // as long
+        let mut bases = 0;
+        for (length, count) in self.length_counts.iter().rev() {
+            bases += length * count;
+            if bases * 2 >= self.total_bases {
+                return *length;
+            }
+        }
+        0
+    }
+
+    fn apply_to(self, model: &mut super::db::ActiveModel, source: &str) {
+        let n50 = self.n50();
+        model.source = Set(source.to_string());
+        model.read_count = Set(self.read_count);
+        model.total_bases = Set(self.total_bases);
+        model.n50 = Set(n50);
+        model.mean_qscore =
+            Set((self.qscore_count > 0).then(|| self.qscore_sum / self.qscore_count as f64));
+        model.barcode_counts = Set(serde_json::to_value(self.barcode_counts).unwrap());
+    }
+}
+
+fn is_sequencing_summary(filename: &str) -> bool {
+    filename.starts_with("sequencing_summary")
+        && (filename.ends_with(".txt") || filename.ends_with(".txt.gz"))
+}
+
+fn is_fastq(filename: &str) -> bool {
+    let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+    filename.ends_with(".fastq") || filename.ends_with(".fq")
+}
+
+fn barcode_from_path(path: &str) -> Option<&str> {
+    // Demultiplexed outputs are grouped in barcodeNN/ or unclassified/
+    path.split('/')
+        .rev()
+        .skip(1)
+        .find(|segment| segment.starts_with("barcode") || *segment == "unclassified")
+}
+
+fn mean_qscore(quality: &str) -> Option<f64> {
+    // Average the error probabilities rather than the phred scores, as
+    // Dorado does for mean_qscore_template
+    if quality.is_empty() {
+        return None;
+    }
+    let error_sum: f64 = quality
+        .bytes()
+        .map(|q| 10f64.powf(-(q.saturating_sub(33) as f64) / 10.0))
+        .sum();
+    Some(-10.0 * (error_sum / quality.len() as f64).log10())
+}
+
+async fn open_lines(
+    s3: &S3Client,
+    submission_id: Uuid,
+    path: &str,
+) -> Result<Lines<Box<dyn AsyncBufRead + Unpin + Send>>> {
+    let config = Config::from_env();
+    let object = s3
+        .get_object()
+        .bucket(&config.s3_bucket)
+        .key(format!(
+            "{}/outputs/{}/{}",
+            config.s3_prefix, submission_id, path
+        ))
+        .send()
+        .await?;
+
+    let body = object.body.into_async_read();
+    let reader: Box<dyn AsyncBufRead + Unpin + Send> = if path.ends_with(".gz") {
+        // bgzip and concatenated files are made of several gzip members
+        let mut decoder = GzipDecoder::new(body);
+        decoder.multiple_members(true);
+        Box::new(BufReader::new(decoder))
+    } else {
+        Box::new(body)
+    };
+
+    Ok(reader.lines())
+}
+
+async fn add_sequencing_summary(
+    metrics: &mut MetricsAccumulator,
+    s3: &S3Client,
+    submission_id: Uuid,
+    path: &str,
+) -> Result<()> {
+    let mut lines = open_lines(s3, submission_id, path).await?;
+    let header = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow!("{} is empty", path))?;
+    let columns: Vec<&str> = header.split('\t').collect();
+    let column = |name: &str| columns.iter().position(|column| *column == name);
+    let length_column = column("sequence_length_template")
+        .ok_or_else(|| anyhow!("{} has no sequence_length_template column", path))?;
+    let qscore_column = column("mean_qscore_template");
+    let barcode_column = column("barcode_arrangement");
+
+    while let Some(line) = lines.next_line().await? {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let length = match fields.get(length_column).and_then(|f| f.parse().ok()) {
+            Some(length) => length,
+            None => continue,
+        };
+        let qscore = qscore_column
+            .and_then(|i| fields.get(i))
+            .and_then(|f| f.parse().ok());
+        let barcode = barcode_column.and_then(|i| fields.get(i)).copied();
+        metrics.add_read(length, qscore, barcode);
+    }
+
+    Ok(())
+}
+
+async fn add_fastq(
+    metrics: &mut MetricsAccumulator,
+    s3: &S3Client,
+    submission_id: Uuid,
+    path: &str,
+) -> Result<()> {
+    let mut lines = open_lines(s3, submission_id, path).await?;
+    let path_barcode = barcode_from_path(path);
+
+    // Records are four lines: @header, sequence, +, quality
+    while let Some(header) = lines.next_line().await? {
+        let (Some(sequence), Some(_), Some(quality)) = (
+            lines.next_line().await?,
+            lines.next_line().await?,
+            lines.next_line().await?,
+        ) else {
+            return Err(anyhow!("{} ends with an incomplete record", path));
+        };
+        let barcode = header
+            .split_whitespace()
+            .find_map(|tag| tag.strip_prefix("barcode="))
+            .or(path_barcode);
+        metrics.add_read(sequence.len() as i64, mean_qscore(&quality), barcode);
+    }
+
+    Ok(())
+}
+
+fn is_run_scoped(path: &str) -> bool {
+    // Under a {run_id}/ directory, as written by runs given their run id
+    path.split_once('/')
+        .is_some_and(|(run_id, _)| run_id.parse::<i64>().is_ok())
+}
+
+async fn compute_metrics(
+    s3: &Arc<S3Client>,
+    submission_id: Uuid,
+    run_id: i64,
+) -> Result<(MetricsAccumulator, String)> {
+    // Only the run's own outputs, as all runs of a submission share its
+    // outputs prefix. Runs from before the run id was passed to the workload
+    // wrote to the outputs root, their metrics are computed from the outputs
+    // outside any run's directory and the source says so.
+    let run_prefix = format!("{}/", run_id);
+    let (objects, _) =
+        crate::external::s3::services::list_outputs(s3, submission_id, Some(&run_prefix), None)
+            .await?;
+    let (objects, layout) = if objects.is_empty() {
+        let (objects, _) =
+            crate::external::s3::services::list_outputs(s3, submission_id, None, None).await?;
+        (objects, " (flat layout)")
+    } else {
+        (objects, "")
+    };
+    let outputs: Vec<OutputObjectResponse> = objects
+        .into_iter()
+        .map(|object| object.into())
+        .filter(|output: &OutputObjectResponse| layout.is_empty() || !is_run_scoped(&output.path))
+        .collect();
+
+    // Prefer the sequencing summaries, which are much smaller than the reads,
+    // and only parse the FASTQ files when there are none
+    let summaries: Vec<&OutputObjectResponse> = outputs
+        .iter()
+        .filter(|output| is_sequencing_summary(&output.filename))
+        .collect();
+    let mut metrics = MetricsAccumulator::default();
+    let source = if !summaries.is_empty() {
+        for summary in summaries {
+            add_sequencing_summary(&mut metrics, s3, submission_id, &summary.path).await?;
+        }
+        "sequencing_summary"
+    } else {
+        let fastqs: Vec<&OutputObjectResponse> = outputs
+            .iter()
+            .filter(|output| is_fastq(&output.filename))
+            .collect();
+        if fastqs.is_empty() {
+            return Err(anyhow!(
+                "No sequencing summary or FASTQ outputs found under {} or the outputs root",
+                run_prefix
+            ));
+        }
+        for fastq in fastqs {
+            add_fastq(&mut metrics, s3, submission_id, &fastq.path).await?;
+        }
+        "fastq"
+    };
+
+    Ok((metrics, format!("{}{}", source, layout)))
+}
+
+async fn record_metrics(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    submission_id: Uuid,
+    run_id: i64,
+    previous: Option<super::db::Model>,
+) -> Result<()> {
+    // Store the metrics, or the failure and when to try again
+    let attempts = previous.as_ref().map_or(0, |previous| previous.attempts) + 1;
+    let is_new = previous.is_none();
+    let mut model = match previous {
+        Some(previous) => previous.into_active_model(),
+        None => super::db::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            submission_id: Set(submission_id),
+            run_id: Set(run_id),
+            source: Set(String::new()),
+            read_count: Set(0),
+            total_bases: Set(0),
+            n50: Set(0),
+            mean_qscore: Set(None),
+            barcode_counts: Set(serde_json::json!({})),
+            ..Default::default()
+        },
+    };
+    model.time_added_utc = Set(Utc::now().naive_utc());
+    model.attempts = Set(attempts);
+
+    match compute_metrics(s3, submission_id, run_id).await {
+        Ok((metrics, source)) => {
+            metrics.apply_to(&mut model, &source);
+            model.error = Set(None);
+            model.next_attempt_on = Set(None);
+        }
+        Err(e) => {
+            println!(
+                "Failed to compute metrics for submission {} run {} (attempt {}): {}",
+                submission_id, run_id, attempts, e
+            );
+            let delay = RETRY_DELAY_MINUTES * 2_i64.pow((attempts - 1) as u32);
+            model.error = Set(Some(e.to_string()));
+            model.next_attempt_on = Set((attempts < MAX_ATTEMPTS)
+                .then(|| Utc::now().naive_utc() + Duration::minutes(delay)));
+        }
+    }
+    if is_new {
+        model.insert(db).await?;
+    } else {
+        model.update(db).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn compute_pending_metrics(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    tracker: &PodTracker,
+) {
+    // Compute the metrics of runs that have succeeded since the last check,
+    // and retry failed computations once their backoff has passed
+    let pods = match crate::external::k8s::services::get_pods(tracker) {
+        Ok(pods) => pods,
+        Err(_) => return,
+    };
+
+    for pod in pods.iter().filter(|pod| pod.latest_status == "Succeeded") {
+        let run_id = pod.run_id as i64;
+        let submission_exists = crate::submissions::db::Entity::find_by_id(pod.submission_id)
+            .one(db)
+            .await
+            .is_ok_and(|submission| submission.is_some());
+        if !submission_exists {
+            continue;
+        }
+        let previous = match super::db::Entity::find()
+            .filter(super::db::Column::SubmissionId.eq(pod.submission_id))
+            .filter(super::db::Column::RunId.eq(run_id))
+            .one(db)
+            .await
+        {
+            Ok(previous) => previous,
+            Err(_) => continue,
+        };
+        let due = match &previous {
+            None => true,
+            Some(previous) => previous
+                .next_attempt_on
+                .is_some_and(|next_attempt_on| next_attempt_on <= Utc::now().naive_utc()),
+        };
+        if !due {
+            continue;
+        }
+
+        if let Err(e) = record_metrics(db, s3, pod.submission_id, run_id, previous).await {
+            println!(
+                "Failed to record metrics for submission {} run {}: {}",
+                pod.submission_id, run_id, e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_n50() {
+        let mut metrics = MetricsAccumulator::default();
+        for length in [2, 3, 4, 5, 6, 7, 8] {
+            metrics.add_read(length, None, None);
+        }
+
+        // 35 bases in total, 8 + 7 + 6 = 21 reach half of them
+        assert_eq!(metrics.n50(), 6);
+        assert_eq!(MetricsAccumulator::default().n50(), 0);
+    }
+
+    #[test]
+    fn averages_error_probabilities() {
+        assert_eq!(mean_qscore(""), None);
+        assert!((mean_qscore("5555").unwrap() - 20.0).abs() < 1e-9);
+        // Q10 and Q30 average to an error of 0.0505, not to Q20
+        assert!((mean_qscore("+?").unwrap() - 12.967).abs() < 1e-3);
+    }
+
+    #[test]
+    fn finds_barcodes_in_directories() {
+        assert_eq!(
+            barcode_from_path("1/pass/barcode01/reads.fastq"),
+            Some("barcode01")
+        );
+        assert_eq!(
+            barcode_from_path("unclassified/reads.fastq"),
+            Some("unclassified")
+        );
+        assert_eq!(barcode_from_path("barcode01.fastq"), None);
+    }
+
+    #[test]
+    fn recognises_output_files() {
+        assert!(is_fastq("reads.fastq.gz"));
+        assert!(is_fastq("reads.fq"));
+        assert!(!is_fastq("reads.bam"));
+        assert!(is_sequencing_summary("sequencing_summary_abc.txt.gz"));
+        assert!(!is_sequencing_summary("summary.txt"));
+    }
+
+    #[test]
+    fn recognises_run_scoped_outputs() {
+        assert!(is_run_scoped("2/pass/reads.fastq"));
+        assert!(!is_run_scoped("pass/reads.fastq"));
+        assert!(!is_run_scoped("2"));
+    }
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "run_queue")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub run_id: i64,
+    pub priority: i32,        // Higher is dispatched first
+    pub requested_by: String, // Keycloak subject, for the per-user limit
+    pub requester_username: Option<String>,
+    pub status: QueueStatus,
+    pub error: Option<String>, // Why the last dispatch attempt failed
+    pub queued_on: NaiveDateTime,
+    pub dispatched_on: Option<NaiveDateTime>,
+    pub attempt: i32,
+    pub original_run_id: Option<i64>, // Run id of the first attempt
+    pub not_before: Option<NaiveDateTime>, // Not dispatched earlier, ie. retry backoff
+    pub outcome: Option<String>,      // Succeeded, or the failure reason, once finished
+    pub finished_on: Option<NaiveDateTime>,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum, ToSchema, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "Enum", enum_name = "queue_status")]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    #[sea_orm(string_value = "queued")]
+    Queued,
+    #[sea_orm(string_value = "dispatched")]
+    Dispatched,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+}
+
+impl Related<crate::submissions::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submissions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
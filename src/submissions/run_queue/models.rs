@@ -0,0 +1,139 @@
+use super::db::QueueStatus;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Failures that are likely transient, when the submission does not set its own
+pub const DEFAULT_RETRY_REASONS: [&str; 7] = [
+    "Evicted",
+    "Preempted",
+    "Preempting",
+    "NodeLost",
+    "Shutdown",
+    "ImagePullBackOff",
+    "ErrImagePull",
+];
+
+#[derive(ToSchema, Serialize, Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: i32,
+    pub backoff_seconds: i32, // Before the second attempt, doubled for each further one
+    pub retryable_reasons: Vec<String>,
+}
+
+impl From<&crate::submissions::db::Model> for RetryPolicy {
+    fn from(model: &crate::submissions::db::Model) -> Self {
+        let retryable_reasons = model
+            .retry_reasons
+            .clone()
+            .and_then(|reasons| serde_json::from_value(reasons).ok())
+            .unwrap_or_else(|| {
+                DEFAULT_RETRY_REASONS
+                    .iter()
+                    .map(|reason| reason.to_string())
+                    .collect()
+            });
+
+        Self {
+            max_attempts: model.retry_max_attempts,
+            backoff_seconds: model.retry_backoff_seconds,
+            retryable_reasons,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn retries(&self, attempt: i32, reason: &str) -> bool {
+        attempt < self.max_attempts && self.retryable_reasons.iter().any(|r| r == reason)
+    }
+
+    pub fn backoff(&self, attempt: i32) -> chrono::Duration {
+        // Exponential, capped so the multiplication cannot overflow
+        let factor = 2_i64.pow((attempt - 1).clamp(0, 16) as u32);
+        chrono::Duration::seconds(self.backoff_seconds as i64 * factor)
+    }
+}
+
+#[derive(ToSchema, Deserialize, Default)]
+pub struct ExecuteOptions {
+    pub priority: Option<i32>, // Higher is dispatched first, defaults to 0
+    pub not_before: Option<DateTime<Utc>>, // Defer the run, ie. to the night
+    pub schedule: Option<String>, // Cron expression in UTC to run it repeatedly, Sunday is 0
+}
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct QueuedRun {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub run_id: i64,
+    pub priority: i32,
+    pub status: QueueStatus,
+    pub position: Option<u64>, // 1 is next to be dispatched, None once dispatched
+    pub error: Option<String>,
+    pub queued_on: NaiveDateTime,
+    pub dispatched_on: Option<NaiveDateTime>,
+    pub attempt: i32,
+    pub original_run_id: Option<i64>,
+    pub not_before: Option<NaiveDateTime>,
+    pub outcome: Option<String>,
+    pub finished_on: Option<NaiveDateTime>,
+}
+
+impl From<(super::db::Model, Option<u64>)> for QueuedRun {
+    fn from((model, position): (super::db::Model, Option<u64>)) -> Self {
+        Self {
+            id: model.id,
+            submission_id: model.submission_id,
+            run_id: model.run_id,
+            priority: model.priority,
+            status: model.status,
+            position,
+            error: model.error,
+            queued_on: model.queued_on,
+            dispatched_on: model.dispatched_on,
+            attempt: model.attempt,
+            original_run_id: model.original_run_id,
+            not_before: model.not_before,
+            outcome: model.outcome,
+            finished_on: model.finished_on,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff_seconds: 30,
+            retryable_reasons: vec!["Evicted".to_string()],
+        }
+    }
+
+    #[test]
+    fn retries_listed_reasons_until_max_attempts() {
+        let policy = policy();
+        assert!(policy.retries(1, "Evicted"));
+        assert!(policy.retries(2, "Evicted"));
+        assert!(!policy.retries(3, "Evicted"));
+        assert!(!policy.retries(1, "OOMKilled"));
+    }
+
+    #[test]
+    fn doubles_backoff_for_each_attempt() {
+        let policy = policy();
+        assert_eq!(policy.backoff(1), chrono::Duration::seconds(30));
+        assert_eq!(policy.backoff(2), chrono::Duration::seconds(60));
+        assert_eq!(policy.backoff(3), chrono::Duration::seconds(120));
+    }
+
+    #[test]
+    fn caps_backoff_exponent() {
+        let policy = policy();
+        assert_eq!(policy.backoff(0), chrono::Duration::seconds(30));
+        assert_eq!(policy.backoff(i32::MAX), policy.backoff(17));
+    }
+}
@@ -0,0 +1,479 @@
+use super::db::QueueStatus;
+use super::models::RetryPolicy;
+use crate::config::Config;
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::services::{delete_workload, get_pods, workload_name};
+use crate::external::k8s::tracker::PodTracker;
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+// A dispatched run counts against the limits until its pod is tracked, or
+// for this long if it never appears
+const DISPATCH_GRACE_MINUTES: i64 = 10;
+
+// Image pulls are retried by the kubelet forever, a run stuck pulling for this
+// long after dispatch is failed so the retry policy can apply
+const IMAGE_PULL_TIMEOUT_MINUTES: i64 = 15;
+
+// A run the cluster rejected is not dispatched again for this long, so it
+// does not hold up the runs queued behind it
+const REJECTED_DISPATCH_BACKOFF_MINUTES: i64 = 10;
+
+enum DispatchError {
+    AlreadyExists, // Submitted by an earlier pass whose update was lost
+    Rejected,      // The request itself is invalid, ie. 422 Unprocessable
+    Unavailable,   // The cluster could not be reached, or failed to answer
+}
+
+fn classify_dispatch_error(err: &anyhow::Error) -> DispatchError {
+    match err.downcast_ref::<kube::Error>() {
+        Some(kube::Error::Api(response)) if response.code == 409 => DispatchError::AlreadyExists,
+        Some(kube::Error::Api(response))
+            if (400..500).contains(&response.code) && response.code != 429 =>
+        {
+            DispatchError::Rejected
+        }
+        _ => DispatchError::Unavailable,
+    }
+}
+
+pub async fn enqueue<C: ConnectionTrait>(
+    db: &C,
+    submission_id: Uuid,
+    priority: i32,
+    requested_by: &str,
+    requester_username: &str,
+) -> Result<super::db::Model> {
+    // The run id is allocated now, so the queued run can be referred to
+    let run_id = crate::submissions::services::allocate_run_id(db, submission_id).await? as i64;
+
+    Ok(super::db::Entity::insert(super::db::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        submission_id: Set(submission_id),
+        run_id: Set(run_id),
+        priority: Set(priority),
+        requested_by: Set(requested_by.to_string()),
+        requester_username: Set(Some(requester_username.to_string())),
+        status: Set(QueueStatus::Queued),
+        error: Set(None),
+        queued_on: Set(Utc::now().naive_utc()),
+        dispatched_on: Set(None),
+        attempt: Set(1),
+        original_run_id: Set(Some(run_id)),
+        not_before: Set(None),
+        outcome: Set(None),
+        finished_on: Set(None),
+    })
+    .exec_with_returning(db)
+    .await?)
+}
+
+async fn enqueue_retry(
+    db: &DatabaseConnection,
+    failed: &super::db::Model,
+    not_before: NaiveDateTime,
+) -> Result<super::db::Model> {
+    // Each attempt is a new workload with its own run id, linked to the first
+    let run_id =
+        crate::submissions::services::allocate_run_id(db, failed.submission_id).await? as i64;
+
+    Ok(super::db::Entity::insert(super::db::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        submission_id: Set(failed.submission_id),
+        run_id: Set(run_id),
+        priority: Set(failed.priority),
+        requested_by: Set(failed.requested_by.clone()),
+        requester_username: Set(failed.requester_username.clone()),
+        status: Set(QueueStatus::Queued),
+        error: Set(None),
+        queued_on: Set(Utc::now().naive_utc()),
+        dispatched_on: Set(None),
+        attempt: Set(failed.attempt + 1),
+        original_run_id: Set(Some(failed.original_run_id.unwrap_or(failed.run_id))),
+        not_before: Set(Some(not_before)),
+        outcome: Set(None),
+        finished_on: Set(None),
+    })
+    .exec_with_returning(db)
+    .await?)
+}
+
+pub async fn cancel(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+    run_id: i64,
+) -> Result<Option<super::db::Model>> {
+    // Only runs still waiting can be cancelled, the update is conditional so
+    // one being dispatched at the same time is left alone
+    super::db::Entity::update_many()
+        .set(super::db::ActiveModel {
+            status: Set(QueueStatus::Cancelled),
+            ..Default::default()
+        })
+        .filter(super::db::Column::SubmissionId.eq(submission_id))
+        .filter(super::db::Column::RunId.eq(run_id))
+        .filter(super::db::Column::Status.eq(QueueStatus::Queued))
+        .exec(db)
+        .await?;
+
+    Ok(super::db::Entity::find()
+        .filter(super::db::Column::SubmissionId.eq(submission_id))
+        .filter(super::db::Column::RunId.eq(run_id))
+        .one(db)
+        .await?)
+}
+
+pub async fn position(db: &DatabaseConnection, queued: &super::db::Model) -> Result<Option<u64>> {
+    // Runs ahead are those of higher priority, or queued earlier with the same
+    if queued.status != QueueStatus::Queued {
+        return Ok(None);
+    }
+
+    let ahead = super::db::Entity::find()
+        .filter(super::db::Column::Status.eq(QueueStatus::Queued))
+        .filter(
+            Condition::any()
+                .add(super::db::Column::Priority.gt(queued.priority))
+                .add(
+                    Condition::all()
+                        .add(super::db::Column::Priority.eq(queued.priority))
+                        .add(super::db::Column::QueuedOn.lt(queued.queued_on)),
+                ),
+        )
+        .count(db)
+        .await?;
+
+    Ok(Some(ahead + 1))
+}
+
+#[derive(Default)]
+struct RunningWorkloads {
+    total: u64,
+    per_user: HashMap<String, u64>,
+}
+
+impl RunningWorkloads {
+    fn add(&mut self, requester: Option<&str>) {
+        self.total += 1;
+        if let Some(requester) = requester {
+            *self.per_user.entry(requester.to_string()).or_default() += 1;
+        }
+    }
+
+    fn for_user(&self, requester: &str) -> u64 {
+        self.per_user.get(requester).copied().unwrap_or(0)
+    }
+}
+
+async fn running_workloads(
+    db: &DatabaseConnection,
+    tracker: &PodTracker,
+) -> Result<RunningWorkloads> {
+    let mut running = RunningWorkloads::default();
+    let pods = get_pods(tracker)?;
+    for pod in pods
+        .iter()
+        .filter(|pod| matches!(pod.latest_status.as_str(), "Pending" | "Running"))
+    {
+        running.add(pod.requester.as_deref());
+    }
+
+    // Recently dispatched runs whose pods are not tracked yet
+    let tracked: HashSet<(Uuid, i64)> = pods
+        .iter()
+        .map(|pod| (pod.submission_id, pod.run_id as i64))
+        .collect();
+    let recently_dispatched = super::db::Entity::find()
+        .filter(super::db::Column::Status.eq(QueueStatus::Dispatched))
+        .filter(
+            super::db::Column::DispatchedOn
+                .gt(Utc::now().naive_utc() - Duration::minutes(DISPATCH_GRACE_MINUTES)),
+        )
+        .all(db)
+        .await?;
+    for queued in recently_dispatched
+        .iter()
+        .filter(|queued| !tracked.contains(&(queued.submission_id, queued.run_id)))
+    {
+        running.add(Some(&queued.requested_by));
+    }
+
+    Ok(running)
+}
+
+pub async fn dispatch(db: &DatabaseConnection, tracker: &PodTracker, kube: &KubeClientProvider) {
+    // Submit queued runs in priority order while the global and per-user
+    // limits of concurrently running workloads allow it
+    let config = Config::from_env();
+    let mut running = match running_workloads(db, tracker).await {
+        Ok(running) => running,
+        Err(_) => return, // Limits cannot be enforced without the run states
+    };
+
+    let queued = match super::db::Entity::find()
+        .filter(super::db::Column::Status.eq(QueueStatus::Queued))
+        .filter(
+            Condition::any()
+                .add(super::db::Column::NotBefore.is_null())
+                .add(super::db::Column::NotBefore.lte(Utc::now().naive_utc())),
+        )
+        .order_by_desc(super::db::Column::Priority)
+        .order_by_asc(super::db::Column::QueuedOn)
+        .all(db)
+        .await
+    {
+        Ok(queued) => queued,
+        Err(_) => return,
+    };
+
+    for queued in queued {
+        if running.total >= config.max_running_workloads {
+            break;
+        }
+        if running.for_user(&queued.requested_by) >= config.max_running_workloads_per_user {
+            continue;
+        }
+
+        let result = crate::submissions::services::create_workload(db, kube, &queued).await;
+        let error = result.as_ref().err().map(classify_dispatch_error);
+        let mut model = queued.clone().into_active_model();
+        match (&result, &error) {
+            (Ok(()), _) | (Err(_), Some(DispatchError::AlreadyExists)) => {
+                model.status = Set(QueueStatus::Dispatched);
+                model.dispatched_on = Set(Some(Utc::now().naive_utc()));
+                model.error = Set(None);
+                running.add(Some(&queued.requested_by));
+            }
+            (Err(e), _) => {
+                println!(
+                    "Failed to dispatch submission {} run {}: {}",
+                    queued.submission_id, queued.run_id, e
+                );
+                model.error = Set(Some(e.to_string()));
+                if matches!(error, Some(DispatchError::Rejected)) {
+                    model.not_before = Set(Some(
+                        Utc::now().naive_utc()
+                            + Duration::minutes(REJECTED_DISPATCH_BACKOFF_MINUTES),
+                    ));
+                }
+            }
+        }
+        if let Err(e) = model.update(db).await {
+            println!("Failed to update queued run {}: {}", queued.id, e);
+        }
+
+        // The cluster is likely unavailable, retry on the next pass
+        if matches!(error, Some(DispatchError::Unavailable)) {
+            break;
+        }
+    }
+}
+
+async fn attempt_outcome(
+    db: &DatabaseConnection,
+    kube: &KubeClientProvider,
+    dispatched: &super::db::Model,
+) -> Result<Option<String>> {
+    // The outcome of the attempt's pod, as recorded by the run status
+    // reconciler, or None while it has not finished
+    let run_status = crate::submissions::run_status::db::Entity::find()
+        .filter(
+            crate::submissions::run_status::db::Column::SubmissionId.eq(dispatched.submission_id),
+        )
+        .filter(crate::submissions::run_status::db::Column::RunId.eq(dispatched.run_id))
+        .order_by_desc(crate::submissions::run_status::db::Column::LastUpdated)
+        .one(db)
+        .await?;
+    let run_status = match run_status {
+        Some(run_status) => run_status,
+        None => return Ok(None),
+    };
+
+    let reason = run_status.reason.clone();
+    match run_status.status.as_deref() {
+        Some("Succeeded") => return Ok(Some("Succeeded".to_string())),
+        Some("Failed") => return Ok(Some(reason.unwrap_or_else(|| "Failed".to_string()))),
+        _ => {}
+    }
+
+    // Stuck pulling the image, the workload is removed to fail the attempt
+    let pulling_since = dispatched.dispatched_on.unwrap_or(dispatched.queued_on);
+    let image_pull_failed = matches!(
+        reason.as_deref(),
+        Some("ImagePullBackOff") | Some("ErrImagePull")
+    );
+    if run_status.is_still_kubernetes_resource
+        && image_pull_failed
+        && pulling_since < Utc::now().naive_utc() - Duration::minutes(IMAGE_PULL_TIMEOUT_MINUTES)
+    {
+        delete_workload(
+            kube,
+            &workload_name(dispatched.submission_id, dispatched.run_id),
+        )
+        .await?;
+        return Ok(reason);
+    }
+
+    // Removed from the cluster before finishing, ie. deleted or preempted
+    if !run_status.is_still_kubernetes_resource {
+        return Ok(Some(reason.unwrap_or_else(|| "Removed".to_string())));
+    }
+
+    Ok(None)
+}
+
+pub async fn record_outcomes(db: &DatabaseConnection, kube: &KubeClientProvider) {
+    // Record how dispatched attempts ended, and queue another attempt of
+    // those that failed for a reason the submission's retry policy covers
+    let dispatched = match super::db::Entity::find()
+        .filter(super::db::Column::Status.eq(QueueStatus::Dispatched))
+        .filter(super::db::Column::Outcome.is_null())
+        .all(db)
+        .await
+    {
+        Ok(dispatched) => dispatched,
+        Err(_) => return,
+    };
+
+    for attempt in dispatched {
+        let outcome = match attempt_outcome(db, kube, &attempt).await {
+            Ok(Some(outcome)) => outcome,
+            Ok(None) => continue,
+            Err(e) => {
+                println!(
+                    "Failed to check submission {} run {}: {}",
+                    attempt.submission_id, attempt.run_id, e
+                );
+                continue;
+            }
+        };
+
+        let mut model = attempt.clone().into_active_model();
+        model.outcome = Set(Some(outcome.clone()));
+        model.finished_on = Set(Some(Utc::now().naive_utc()));
+        if let Err(e) = model.update(db).await {
+            println!(
+                "Failed to record outcome of queued run {}: {}",
+                attempt.id, e
+            );
+            continue;
+        }
+
+        if outcome == "Succeeded" {
+            continue;
+        }
+        let policy: RetryPolicy =
+            match crate::submissions::db::Entity::find_by_id(attempt.submission_id)
+                .one(db)
+                .await
+            {
+                Ok(Some(submission)) => (&submission).into(),
+                _ => continue,
+            };
+        if !policy.retries(attempt.attempt, &outcome) {
+            continue;
+        }
+
+        let not_before = match Utc::now()
+            .naive_utc()
+            .checked_add_signed(policy.backoff(attempt.attempt))
+        {
+            Some(not_before) => not_before,
+            None => {
+                println!(
+                    "Backoff of submission {} run {} is out of range, not retrying",
+                    attempt.submission_id, attempt.run_id
+                );
+                continue;
+            }
+        };
+        match enqueue_retry(db, &attempt, not_before).await {
+            Ok(retry) => println!(
+                "Retrying submission {} run {} ({}) as run {}, attempt {}",
+                attempt.submission_id, attempt.run_id, outcome, retry.run_id, retry.attempt
+            ),
+            Err(e) => println!(
+                "Failed to queue a retry of submission {} run {}: {}",
+                attempt.submission_id, attempt.run_id, e
+            ),
+        }
+    }
+}
+
+pub async fn get_for_submission(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+) -> Result<Vec<super::models::QueuedRun>> {
+    // All attempts of the submission's runs, with the queue position of
+    // those still waiting
+    let queued = super::db::Entity::find()
+        .filter(super::db::Column::SubmissionId.eq(submission_id))
+        .order_by_asc(super::db::Column::QueuedOn)
+        .all(db)
+        .await?;
+
+    let mut runs = Vec::with_capacity(queued.len());
+    for queued in queued {
+        let position = position(db, &queued).await?;
+        runs.push((queued, position).into());
+    }
+
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(code: u16) -> anyhow::Error {
+        kube::Error::Api(kube::core::ErrorResponse {
+            status: "Failure".to_string(),
+            message: String::new(),
+            reason: String::new(),
+            code,
+        })
+        .into()
+    }
+
+    #[test]
+    fn classifies_conflicts_as_already_existing() {
+        assert!(matches!(
+            classify_dispatch_error(&api_error(409)),
+            DispatchError::AlreadyExists
+        ));
+    }
+
+    #[test]
+    fn classifies_client_errors_as_rejected() {
+        assert!(matches!(
+            classify_dispatch_error(&api_error(422)),
+            DispatchError::Rejected
+        ));
+        assert!(matches!(
+            classify_dispatch_error(&api_error(403)),
+            DispatchError::Rejected
+        ));
+    }
+
+    #[test]
+    fn classifies_other_errors_as_unavailable() {
+        assert!(matches!(
+            classify_dispatch_error(&api_error(429)),
+            DispatchError::Unavailable
+        ));
+        assert!(matches!(
+            classify_dispatch_error(&api_error(503)),
+            DispatchError::Unavailable
+        ));
+        assert!(matches!(
+            classify_dispatch_error(&anyhow::anyhow!("connection refused")),
+            DispatchError::Unavailable
+        ));
+    }
+}
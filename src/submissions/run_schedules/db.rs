@@ -0,0 +1,37 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "run_schedules")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub schedule: Option<String>, // Cron expression in UTC, None for a one-off deferred run
+    pub next_run_on: NaiveDateTime,
+    pub priority: i32,
+    pub requested_by: String, // Keycloak subject, the queued runs count against their limit
+    pub requester_username: Option<String>,
+    pub created_on: NaiveDateTime,
+    pub last_run_on: Option<NaiveDateTime>,
+    pub last_run_id: Option<i64>, // Run id of the last run queued by the schedule
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+}
+
+impl Related<crate::submissions::db::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Submissions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,47 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct ScheduledRun {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub schedule: Option<String>, // Cron expression in UTC, None for a one-off run
+    pub next_run_on: NaiveDateTime,
+    pub priority: i32,
+    pub requester_username: Option<String>,
+    pub created_on: NaiveDateTime,
+    pub last_run_on: Option<NaiveDateTime>,
+    pub last_run_id: Option<i64>,
+}
+
+impl From<super::db::Model> for ScheduledRun {
+    fn from(model: super::db::Model) -> Self {
+        Self {
+            id: model.id,
+            submission_id: model.submission_id,
+            schedule: model.schedule,
+            next_run_on: model.next_run_on,
+            priority: model.priority,
+            requester_username: model.requester_username,
+            created_on: model.created_on,
+            last_run_on: model.last_run_on,
+            last_run_id: model.last_run_id,
+        }
+    }
+}
+
+#[derive(ToSchema, Deserialize)]
+pub struct ScheduleUpdate {
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>, // Next run, or the earliest for a cron schedule
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    pub schedule: Option<Option<String>>, // Set to null to run only once more
+    #[serde(default)]
+    pub priority: Option<i32>,
+}
@@ -0,0 +1,367 @@
+use super::models::ScheduleUpdate;
+use anyhow::{anyhow, Result};
+use chrono::{Duration, NaiveDateTime, Utc};
+use cron::Schedule;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
+use std::str::FromStr;
+use uuid::Uuid;
+
+// Schedules firing more often than this would flood the queue
+const MIN_INTERVAL_MINUTES: i64 = 15;
+
+// Upcoming occurrences compared against the minimum interval
+const CHECKED_OCCURRENCES: usize = 50;
+
+fn standard_day_of_week(day: &str) -> Result<u32> {
+    // Standard cron counts the days from Sunday as 0 (or 7), the cron crate
+    // from Sunday as 1
+    match day.parse::<u32>() {
+        Ok(day) if day <= 7 => Ok(day % 7 + 1),
+        _ => Err(anyhow!("Invalid day of week: {}", day)),
+    }
+}
+
+fn standard_days_of_week(field: &str) -> Result<String> {
+    // Numeric days, ranges and steps are translated, names (ie. MON-FRI) are
+    // the same in both and kept as they are
+    let mut days = vec![];
+    for item in field.split(',') {
+        let (base, step) = match item.split_once('/') {
+            Some((base, step)) => (base, Some(step)),
+            None => (item, None),
+        };
+        let step = step.map(|step| format!("/{}", step)).unwrap_or_default();
+        if base == "*" || base == "?" || base.chars().any(|c| c.is_ascii_alphabetic()) {
+            days.push(item.to_string());
+            continue;
+        }
+        match base.split_once('-') {
+            // Ranges up to 7 end on Sunday, which comes first for the crate
+            Some((from, "7")) if step.is_empty() => {
+                days.push(format!("{}-7", standard_day_of_week(from)?));
+                days.push("1".to_string());
+            }
+            Some((_, "7")) => {
+                return Err(anyhow!(
+                    "Invalid day of week: {}, use 0 for Sunday with steps",
+                    item
+                ))
+            }
+            Some((from, to)) => days.push(format!(
+                "{}-{}{}",
+                standard_day_of_week(from)?,
+                standard_day_of_week(to)?,
+                step
+            )),
+            None => days.push(format!("{}{}", standard_day_of_week(base)?, step)),
+        }
+    }
+
+    Ok(days.join(","))
+}
+
+fn to_cron_schedule(schedule: &str) -> Result<Schedule> {
+    // Schedules are kept as given: five fields, or six and seven with the
+    // seconds first and the year last. The day of week counts from Sunday as
+    // 0 in all of them, as in standard cron.
+    let fields = schedule.split_whitespace().collect::<Vec<_>>();
+    let (seconds, fields) = match fields.len() {
+        5 => ("0", &fields[..]),
+        6 | 7 => (fields[0], &fields[1..]),
+        _ => return Err(anyhow!("Invalid schedule: expected 5, 6 or 7 fields")),
+    };
+    let (day_of_month, day_of_week) = (fields[2], fields[4]);
+
+    // Standard cron runs when either day matches, the cron crate only when
+    // both do, so only one of them may be restricted
+    let restricted = |field: &str| field != "*" && field != "?";
+    if restricted(day_of_month) && restricted(day_of_week) {
+        return Err(anyhow!(
+            "Invalid schedule: restrict either the day of month or the day of week"
+        ));
+    }
+
+    let schedule = format!(
+        "{} {} {} {} {} {} {}",
+        seconds,
+        fields[0],
+        fields[1],
+        day_of_month,
+        fields[3],
+        standard_days_of_week(day_of_week)?,
+        fields.get(5).copied().unwrap_or_default()
+    );
+    Schedule::from_str(schedule.trim_end()).map_err(|e| anyhow!("Invalid schedule: {}", e))
+}
+
+pub fn parse_schedule(schedule: &str) -> Result<String> {
+    // Validated and returned with its whitespace normalised, it is translated
+    // to the cron crate's syntax whenever the next occurrence is computed
+    let schedule = schedule.split_whitespace().collect::<Vec<_>>().join(" ");
+    let upcoming = to_cron_schedule(&schedule)?
+        .upcoming(Utc)
+        .take(CHECKED_OCCURRENCES)
+        .collect::<Vec<_>>();
+    if upcoming
+        .windows(2)
+        .any(|pair| pair[1] - pair[0] < Duration::minutes(MIN_INTERVAL_MINUTES))
+    {
+        return Err(anyhow!(
+            "Invalid schedule: runs must be at least {} minutes apart",
+            MIN_INTERVAL_MINUTES
+        ));
+    }
+
+    Ok(schedule)
+}
+
+fn next_occurrence(schedule: &str, after: NaiveDateTime) -> Result<NaiveDateTime> {
+    to_cron_schedule(schedule)?
+        .after(&after.and_utc())
+        .next()
+        .map(|next| next.naive_utc())
+        .ok_or_else(|| anyhow!("Schedule {} has no further occurrence", schedule))
+}
+
+pub async fn create(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+    not_before: Option<NaiveDateTime>,
+    schedule: Option<String>,
+    priority: i32,
+    requested_by: &str,
+    requester_username: &str,
+) -> Result<super::db::Model> {
+    // A cron schedule starts at its first occurrence after not_before
+    let now = Utc::now().naive_utc();
+    let next_run_on = match &schedule {
+        Some(schedule) => next_occurrence(schedule, not_before.unwrap_or(now))?,
+        None => not_before.unwrap_or(now),
+    };
+
+    Ok(super::db::Entity::insert(super::db::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        submission_id: Set(submission_id),
+        schedule: Set(schedule),
+        next_run_on: Set(next_run_on),
+        priority: Set(priority),
+        requested_by: Set(requested_by.to_string()),
+        requester_username: Set(Some(requester_username.to_string())),
+        created_on: Set(now),
+        last_run_on: Set(None),
+        last_run_id: Set(None),
+    })
+    .exec_with_returning(db)
+    .await?)
+}
+
+pub async fn reschedule(
+    db: &DatabaseConnection,
+    scheduled: super::db::Model,
+    update: ScheduleUpdate,
+) -> Result<super::db::Model> {
+    // The schedule is expected to be validated with parse_schedule already
+    let schedule = match update.schedule {
+        Some(schedule) => schedule,
+        None => scheduled.schedule.clone(),
+    };
+    let not_before = update.not_before.map(|not_before| not_before.naive_utc());
+    let next_run_on = match (&schedule, not_before) {
+        (Some(schedule), not_before) => {
+            next_occurrence(schedule, not_before.unwrap_or(Utc::now().naive_utc()))?
+        }
+        (None, Some(not_before)) => not_before,
+        (None, None) => scheduled.next_run_on,
+    };
+
+    let priority = update.priority.unwrap_or(scheduled.priority);
+    let mut model = scheduled.into_active_model();
+    model.schedule = Set(schedule);
+    model.next_run_on = Set(next_run_on);
+    model.priority = Set(priority);
+
+    Ok(model.update(db).await?)
+}
+
+pub async fn queue_due(db: &DatabaseConnection) {
+    // Queue the runs of schedules that are due, the dispatcher then submits
+    // them within the concurrency limits like any other run. A recurring
+    // schedule moves on to its next occurrence, missed ones are not caught up
+    let now = Utc::now().naive_utc();
+    let due = match super::db::Entity::find()
+        .filter(super::db::Column::NextRunOn.lte(now))
+        .order_by_asc(super::db::Column::NextRunOn)
+        .all(db)
+        .await
+    {
+        Ok(due) => due,
+        Err(_) => return,
+    };
+
+    for scheduled in due {
+        let (id, submission_id) = (scheduled.id, scheduled.submission_id);
+        match queue_scheduled(db, scheduled, now).await {
+            Ok(Some(run_id)) => println!(
+                "Queued scheduled run {} of submission {} as run {}",
+                id, submission_id, run_id
+            ),
+            Ok(None) => {}
+            Err(e) => println!(
+                "Failed to queue scheduled run {} of submission {}: {}",
+                id, submission_id, e
+            ),
+        }
+    }
+}
+
+async fn queue_scheduled(
+    db: &DatabaseConnection,
+    scheduled: super::db::Model,
+    now: NaiveDateTime,
+) -> Result<Option<i64>> {
+    // The schedule is moved on, only if it is still at the occurrence that
+    // was read, in the same transaction the run is queued in. A concurrent
+    // pass finds it moved on (or waits for this one to commit and then does),
+    // so an occurrence is never queued twice, nor skipped.
+    let txn = db.begin().await?;
+    let next_run_on = scheduled
+        .schedule
+        .as_deref()
+        .and_then(|schedule| next_occurrence(schedule, now).ok());
+    let rows_affected = match next_run_on {
+        Some(next_run_on) => {
+            super::db::Entity::update_many()
+                .col_expr(super::db::Column::NextRunOn, Expr::value(next_run_on))
+                .col_expr(super::db::Column::LastRunOn, Expr::value(now))
+                .filter(super::db::Column::Id.eq(scheduled.id))
+                .filter(super::db::Column::NextRunOn.eq(scheduled.next_run_on))
+                .exec(&txn)
+                .await?
+                .rows_affected
+        }
+        // Run once, or the schedule has ended, ie. a fixed year that has passed
+        None => {
+            super::db::Entity::delete_many()
+                .filter(super::db::Column::Id.eq(scheduled.id))
+                .filter(super::db::Column::NextRunOn.eq(scheduled.next_run_on))
+                .exec(&txn)
+                .await?
+                .rows_affected
+        }
+    };
+    if rows_affected == 0 {
+        return Ok(None);
+    }
+
+    let queued = crate::submissions::run_queue::services::enqueue(
+        &txn,
+        scheduled.submission_id,
+        scheduled.priority,
+        &scheduled.requested_by,
+        scheduled.requester_username.as_deref().unwrap_or_default(),
+    )
+    .await?;
+    if next_run_on.is_some() {
+        super::db::Entity::update_many()
+            .col_expr(super::db::Column::LastRunId, Expr::value(queued.run_id))
+            .filter(super::db::Column::Id.eq(scheduled.id))
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+
+    Ok(Some(queued.run_id))
+}
+
+pub async fn get_for_submission(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+) -> Result<Vec<super::models::ScheduledRun>> {
+    Ok(super::db::Entity::find()
+        .filter(super::db::Column::SubmissionId.eq(submission_id))
+        .order_by_asc(super::db::Column::NextRunOn)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|scheduled| scheduled.into())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn monday() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 12, 16)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn translates_days_of_week() {
+        assert_eq!(standard_days_of_week("0").unwrap(), "1");
+        assert_eq!(standard_days_of_week("7").unwrap(), "1");
+        assert_eq!(standard_days_of_week("1-5").unwrap(), "2-6");
+        assert_eq!(standard_days_of_week("0-6/2").unwrap(), "1-7/2");
+        assert_eq!(standard_days_of_week("1,3").unwrap(), "2,4");
+        assert_eq!(standard_days_of_week("5-7").unwrap(), "6-7,1");
+    }
+
+    #[test]
+    fn keeps_names_and_wildcards() {
+        assert_eq!(standard_days_of_week("MON-FRI").unwrap(), "MON-FRI");
+        assert_eq!(standard_days_of_week("*").unwrap(), "*");
+        assert_eq!(standard_days_of_week("?").unwrap(), "?");
+        assert_eq!(standard_days_of_week("*/2").unwrap(), "*/2");
+    }
+
+    #[test]
+    fn rejects_invalid_days_of_week() {
+        assert!(standard_days_of_week("8").is_err());
+        assert!(standard_days_of_week("5-7/2").is_err());
+    }
+
+    #[test]
+    fn counts_sunday_as_zero_for_all_field_counts() {
+        let sunday = NaiveDate::from_ymd_opt(2024, 12, 22)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        assert_eq!(next_occurrence("0 9 * * 0", monday()).unwrap(), sunday);
+        assert_eq!(next_occurrence("0 0 9 * * 0", monday()).unwrap(), sunday);
+        assert_eq!(
+            next_occurrence("0 0 9 * * 0 2024", monday()).unwrap(),
+            sunday
+        );
+    }
+
+    #[test]
+    fn rejects_both_days_restricted() {
+        assert!(to_cron_schedule("0 9 1 * 1").is_err());
+        assert!(to_cron_schedule("0 9 1 * ?").is_ok());
+        assert!(to_cron_schedule("0 9 * * 1").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_field_counts() {
+        assert!(to_cron_schedule("0 9 * *").is_err());
+        assert!(to_cron_schedule("0 0 9 * * 1 2024 1").is_err());
+    }
+
+    #[test]
+    fn normalises_schedule_whitespace() {
+        assert_eq!(parse_schedule(" 0  9 * * 1 ").unwrap(), "0 9 * * 1");
+    }
+
+    #[test]
+    fn rejects_frequent_schedules() {
+        assert!(parse_schedule("* * * * *").is_err());
+        assert!(parse_schedule("*/5 * * * *").is_err());
+        assert!(parse_schedule("*/15 * * * *").is_ok());
+    }
+}
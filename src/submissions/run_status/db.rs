@@ -17,6 +17,14 @@ pub struct Model {
     pub logs: Json,
     pub time_added_utc: NaiveDateTime,
     pub last_updated: NaiveDateTime,
+    pub run_id: Option<i64>,
+    pub reason: Option<String>, // Container waiting or terminated reason, ie. OOMKilled
+    pub message: Option<String>,
+    pub exit_code: Option<i32>,
+    pub restart_count: i32,
+    pub node_name: Option<String>,
+    pub gpu_type: Option<String>, // Product of the GPUs on the node
+    pub pending_reason: Option<String>, // From the pod's latest warning event
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
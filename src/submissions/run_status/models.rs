@@ -8,6 +8,7 @@ use uuid::Uuid;
 pub struct RunStatus {
     pub id: Uuid,
     pub submission_id: Uuid,
+    pub run_id: Option<i64>,
     pub kubernetes_pod_name: Option<String>,
     pub status: Option<String>,
     pub is_running: bool,
@@ -17,13 +18,50 @@ pub struct RunStatus {
     pub logs: Value,
     pub time_added_utc: NaiveDateTime,
     pub last_updated: NaiveDateTime,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    pub exit_code: Option<i32>,
+    pub restart_count: i32,
+    pub node_name: Option<String>,
+    pub gpu_type: Option<String>,
+    pub pending_reason: Option<String>,
+    pub explanation: Option<String>, // Human readable summary of the above
+}
+
+fn explain(model: &super::db::Model) -> Option<String> {
+    let node = model.node_name.as_deref().unwrap_or("unknown node");
+    match model.reason.as_deref() {
+        Some("OOMKilled") => return Some(format!("Out of memory on node {}", node)),
+        Some("ImagePullBackOff") | Some("ErrImagePull") => {
+            return Some("Could not pull the workflow image".to_string())
+        }
+        Some("CrashLoopBackOff") => {
+            return Some(format!(
+                "Crashed repeatedly on node {} ({} restarts)",
+                node, model.restart_count
+            ))
+        }
+        _ => {}
+    }
+    if let Some(pending_reason) = &model.pending_reason {
+        return Some(format!("Pending: {}", pending_reason));
+    }
+    match (model.status.as_deref(), model.exit_code) {
+        (Some("Failed"), Some(exit_code)) => Some(format!(
+            "Failed with exit code {} on node {}",
+            exit_code, node
+        )),
+        _ => None,
+    }
 }
 
 impl From<super::db::Model> for RunStatus {
     fn from(model: super::db::Model) -> Self {
+        let explanation = explain(&model);
         Self {
             id: model.id,
             submission_id: model.submission_id,
+            run_id: model.run_id,
             kubernetes_pod_name: model.kubernetes_pod_name,
             status: model.status,
             is_running: model.is_running,
@@ -33,6 +71,14 @@ impl From<super::db::Model> for RunStatus {
             logs: model.logs,
             time_added_utc: model.time_added_utc,
             last_updated: model.last_updated,
+            reason: model.reason,
+            message: model.message,
+            exit_code: model.exit_code,
+            restart_count: model.restart_count,
+            node_name: model.node_name,
+            gpu_type: model.gpu_type,
+            pending_reason: model.pending_reason,
+            explanation,
         }
     }
 }
@@ -0,0 +1,122 @@
+use crate::external::k8s::models::PodName;
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::services::{get_node_gpu_type, get_pending_reason, get_pods};
+use crate::external::k8s::tracker::PodTracker;
+use anyhow::Result;
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    IntoActiveModel, QueryFilter, Set,
+};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+async fn save_pod_status(
+    db: &DatabaseConnection,
+    kube: &KubeClientProvider,
+    pod: &PodName,
+    gpu_types: &mut HashMap<String, Option<String>>,
+) -> Result<()> {
+    // Events are only looked up while the pod waits to be scheduled or started
+    let pending_reason = match pod.latest_status.as_str() {
+        "Pending" => get_pending_reason(kube, &pod.name).await.unwrap_or(None),
+        _ => None,
+    };
+
+    // Nodes are looked up once per reconciliation
+    let gpu_type = match &pod.node_name {
+        Some(node_name) => match gpu_types.get(node_name) {
+            Some(gpu_type) => gpu_type.clone(),
+            None => {
+                let gpu_type = get_node_gpu_type(kube, node_name).await.unwrap_or(None);
+                gpu_types.insert(node_name.clone(), gpu_type.clone());
+                gpu_type
+            }
+        },
+        None => None,
+    };
+
+    let existing = super::db::Entity::find()
+        .filter(super::db::Column::KubernetesPodName.eq(pod.name.as_str()))
+        .one(db)
+        .await?;
+    let mut run_status = match existing {
+        Some(run_status) => run_status.into_active_model(),
+        None => super::db::ActiveModel {
+            id: Set(Uuid::new_v4()),
+            submission_id: Set(pod.submission_id),
+            kubernetes_pod_name: Set(Some(pod.name.clone())),
+            logs: Set(serde_json::Value::Array(vec![])),
+            time_added_utc: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        },
+    };
+
+    run_status.run_id = Set(Some(pod.run_id as i64));
+    run_status.status = Set(Some(pod.latest_status.clone()));
+    run_status.is_running = Set(matches!(pod.latest_status.as_str(), "Pending" | "Running"));
+    run_status.is_successful = Set(pod.latest_status == "Succeeded");
+    run_status.is_still_kubernetes_resource = Set(true);
+    run_status.time_started = Set(pod.start_time.map(|time| time.to_rfc3339()));
+    run_status.reason = Set(pod.container.reason.clone());
+    run_status.message = Set(pod.container.message.clone());
+    run_status.exit_code = Set(pod.container.exit_code);
+    run_status.restart_count = Set(pod.container.restart_count);
+    run_status.node_name = Set(pod.node_name.clone());
+    run_status.gpu_type = Set(gpu_type);
+    run_status.pending_reason = Set(pending_reason);
+    run_status.last_updated = Set(Utc::now().naive_utc());
+    run_status.save(db).await?;
+
+    Ok(())
+}
+
+pub async fn reconcile(db: &DatabaseConnection, tracker: &PodTracker, kube: &KubeClientProvider) {
+    // Persist the status of the tracked pods, so it is kept once Kubernetes
+    // has removed them
+    let pods = match get_pods(tracker) {
+        Ok(pods) => pods,
+        Err(_) => return,
+    };
+
+    // Pods may outlive their submission
+    let submission_ids: HashSet<Uuid> = pods.iter().map(|pod| pod.submission_id).collect();
+    let existing_submissions: HashSet<Uuid> = match crate::submissions::db::Entity::find()
+        .filter(crate::submissions::db::Column::Id.is_in(submission_ids))
+        .all(db)
+        .await
+    {
+        Ok(submissions) => submissions.into_iter().map(|obj| obj.id).collect(),
+        Err(_) => return,
+    };
+
+    let mut gpu_types: HashMap<String, Option<String>> = HashMap::new();
+    for pod in pods
+        .iter()
+        .filter(|pod| existing_submissions.contains(&pod.submission_id))
+    {
+        if let Err(e) = save_pod_status(db, kube, pod, &mut gpu_types).await {
+            println!("Failed to save status of pod {}: {}", pod.name, e);
+        }
+    }
+
+    // Pods that are no longer tracked have been removed from the cluster
+    let tracked: Vec<String> = pods.into_iter().map(|pod| pod.name).collect();
+    if let Err(e) = super::db::Entity::update_many()
+        .col_expr(
+            super::db::Column::IsStillKubernetesResource,
+            Expr::value(false),
+        )
+        .col_expr(super::db::Column::IsRunning, Expr::value(false))
+        .col_expr(
+            super::db::Column::LastUpdated,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(super::db::Column::IsStillKubernetesResource.eq(true))
+        .filter(super::db::Column::KubernetesPodName.is_not_in(tracked))
+        .exec(db)
+        .await
+    {
+        println!("Failed to update status of removed pods: {}", e);
+    }
+}
@@ -123,9 +123,25 @@
 //     Ok(())
 // }
 
+use crate::common::auth::{is_admin, Role};
+use crate::config::Config;
+use crate::external::k8s::crd::{
+    Environment, EnvironmentItems, ItemizedField, TrainingWorkload, TrainingWorkloadSpec,
+    ValueField,
+};
+use crate::external::k8s::provider::KubeClientProvider;
+use crate::external::k8s::services::{workload_annotations, workload_labels, workload_name};
 use crate::uploads::db;
 use anyhow::{anyhow, Error, Result};
-use sea_orm::{DatabaseConnection, ModelTrait};
+use axum_keycloak_auth::decode::KeycloakToken;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use kube::{api::PostParams, Api};
+use sea_orm::{
+    sea_query::Expr, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, ModelTrait,
+    QueryFilter,
+};
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub(super) async fn get_input_objects(
     submission_obj: super::db::Model,
@@ -142,3 +158,207 @@ pub(super) async fn get_input_objects(
         Err(_) => Err(anyhow!("Failed to fetch uploads")),
     }
 }
+
+pub async fn allocate_run_id<C: ConnectionTrait>(db: &C, submission_id: Uuid) -> Result<i32> {
+    // Incremented in a single statement, so concurrent executions of the same
+    // submission can never be given the same run id
+    let updated = super::db::Entity::update_many()
+        .col_expr(
+            super::db::Column::LastRunId,
+            Expr::col(super::db::Column::LastRunId).add(1),
+        )
+        .filter(super::db::Column::Id.eq(submission_id))
+        .exec_with_returning(db)
+        .await?;
+
+    match updated.first() {
+        Some(submission) => Ok(submission.last_run_id),
+        None => Err(anyhow!("Submission {} not found", submission_id)),
+    }
+}
+
+pub fn user_can_access(token: &KeycloakToken<Role>, submission_obj: &super::db::Model) -> bool {
+    // Administrators can access all submissions, others only their own
+    is_admin(token) || submission_obj.created_by.as_deref() == Some(token.subject.as_str())
+}
+
+pub fn normalise_output_path(path: &str) -> Option<String> {
+    // Paths relative to the submission's outputs, ie. barcode01/reads.fastq.
+    // Empty, "." and ".." segments are rejected rather than resolved.
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let valid = !path.is_empty()
+        && path
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+
+    valid.then(|| path.to_string())
+}
+
+pub fn create_download_token(submission_id: Uuid, filename: &str) -> Result<String, Error> {
+    // Signed token for an output that can be shared without a Keycloak login
+    let config = Config::from_env();
+    let expiry =
+        chrono::Utc::now() + chrono::Duration::hours(config.download_token_expiry_hours as i64);
+    let claims = super::models::Claims {
+        submission_id,
+        filename: filename.to_string(),
+        exp: expiry.timestamp() as usize,
+    };
+
+    Ok(encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.download_token_secret.as_bytes()),
+    )?)
+}
+
+pub fn decode_download_token(
+    token: &str,
+) -> Result<super::models::Claims, jsonwebtoken::errors::Error> {
+    // Validates the signature and that the token has not expired
+    let config = Config::from_env();
+    let data = decode::<super::models::Claims>(
+        token,
+        &DecodingKey::from_secret(config.download_token_secret.as_bytes()),
+        &Validation::default(),
+    )?;
+
+    Ok(data.claims)
+}
+
+pub async fn create_workload(
+    db: &DatabaseConnection,
+    kube: &KubeClientProvider,
+    queued: &super::run_queue::db::Model,
+) -> Result<()> {
+    let config = Config::from_env();
+    let id = queued.submission_id;
+    let job_name = workload_name(id, queued.run_id);
+
+    // Fetch submission and related uploads
+    let obj = super::db::Entity::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow!("Submission {} not found", id))?;
+
+    let input_objects: Vec<crate::uploads::db::Model> =
+        obj.find_related(crate::uploads::db::Entity).all(db).await?;
+    let input_object_ids: Vec<Uuid> = input_objects.iter().map(|assoc| assoc.id).collect();
+
+    // Map of object ID to its path within the uploaded folder, so the
+    // workload can restore the directory structure (ie. barcodes)
+    let input_object_paths: HashMap<Uuid, String> = input_objects
+        .into_iter()
+        .map(|assoc| (assoc.id, assoc.relative_path))
+        .collect();
+
+    let base_image = format!(
+        "{}:{}",
+        config.submission_base_image, config.submission_base_image_tag,
+    );
+    let labels = workload_labels(
+        id,
+        queued.run_id,
+        &queued.requested_by,
+        &config.submission_base_image_tag,
+    );
+    let annotations = workload_annotations(
+        queued.requester_username.as_deref().unwrap_or_default(),
+        &base_image,
+    );
+    // Create a new TrainingWorkload custom resource
+    let mut training_workload = TrainingWorkload::new(
+        &job_name,
+        TrainingWorkloadSpec {
+            allow_privilege_escalation: Some(ValueField { value: true }),
+            environment: Environment {
+                items: EnvironmentItems {
+                    input_object_ids: ValueField {
+                        value: serde_json::to_string(&input_object_ids)?,
+                    },
+                    input_object_paths: ValueField {
+                        value: serde_json::to_string(&input_object_paths)?,
+                    },
+                    s3_access_key: ValueField {
+                        value: config.s3_access_key.to_string(),
+                    },
+                    s3_bucket_id: ValueField {
+                        value: config.s3_bucket.to_string(),
+                    },
+                    s3_prefix: ValueField {
+                        value: config.s3_prefix.to_string(),
+                    },
+                    s3_secret_key: ValueField {
+                        value: config.s3_secret_key.to_string(),
+                    },
+                    s3_url: ValueField {
+                        value: config.s3_url.to_string(),
+                    },
+                    submission_id: ValueField {
+                        value: id.to_string(),
+                    },
+                    // The workload writes its outputs under
+                    // outputs/{submission_id}/{run_id}/, which the run metrics
+                    // rely on to tell the runs of a submission apart
+                    run_id: ValueField {
+                        value: queued.run_id.to_string(),
+                    },
+                    base_image: ValueField {
+                        value: base_image.clone(),
+                    },
+                },
+            },
+            gpu: ValueField {
+                value: "1".to_string(),
+            },
+            image: ValueField { value: base_image },
+            image_pull_policy: ValueField {
+                value: "Always".to_string(),
+            },
+            labels: Some(ItemizedField::from(&labels)),
+            annotations: Some(ItemizedField::from(&annotations)),
+            name: ValueField {
+                value: job_name.clone(),
+            },
+            run_as_gid: None,
+            run_as_uid: None,
+            run_as_user: None,
+            service_type: None,
+            usage: Some("Submit".to_string()),
+        },
+    );
+    training_workload.metadata.labels = Some(labels);
+    training_workload.metadata.annotations = Some(annotations);
+
+    // Submit the custom resource to Kubernetes
+    let api: Api<TrainingWorkload> = Api::namespaced(kube.client().await?, &config.kube_namespace);
+    api.create(&PostParams::default(), &training_workload)
+        .await
+        .inspect_err(|e| kube.forget_if_unauthorized(e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_relative_output_paths() {
+        assert_eq!(
+            normalise_output_path("barcode01/reads.fastq").as_deref(),
+            Some("barcode01/reads.fastq")
+        );
+        assert_eq!(
+            normalise_output_path("/reads.fastq").as_deref(),
+            Some("reads.fastq")
+        );
+    }
+
+    #[test]
+    fn rejects_non_normalised_output_paths() {
+        for path in ["", "/", "a//b", "./a", "a/../b", "..", "a/"] {
+            assert_eq!(normalise_output_path(path), None, "{}", path);
+        }
+    }
+}
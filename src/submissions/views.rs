@@ -2,58 +2,107 @@ use crate::common::auth::Role;
 use crate::common::filter::{apply_filters, parse_range};
 use crate::common::models::FilterOptions;
 use crate::common::pagination::calculate_content_range;
-use crate::common::sort::generic_sort;
-use crate::external::k8s::crd::{
-    Environment, EnvironmentItems, TrainingWorkload, TrainingWorkloadSpec, ValueField,
-};
+use crate::common::sort::{generic_sort, parse_sort};
+use crate::external::k8s::tracker::PodTracker;
+use crate::external::s3::models::ArchiveFormat;
+use crate::submissions::events::services::EventHub;
 use anyhow::Result;
-use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::Client as S3Client;
 use axum::{
-    debug_handler,
+    body::Body,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing, Json, Router,
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing, Extension, Json, Router,
 };
 use axum_keycloak_auth::{
-    instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
+    decode::KeycloakToken, instance::KeycloakAuthInstance, layer::KeycloakAuthLayer,
+    PassthroughMode,
 };
-use kube::{api::PostParams, Api};
-use rand::Rng;
+use futures::Stream;
 use sea_orm::{
-    query::*, ActiveModelTrait, DatabaseConnection, DeleteResult, EntityTrait, IntoActiveModel,
-    ModelTrait, SqlErr,
+    query::*, ActiveModelTrait, ColumnTrait, DatabaseConnection, DeleteResult, EntityTrait,
+    IntoActiveModel, ModelTrait, SqlErr,
 };
+use sea_query::Order;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
-use std::time::Duration;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 use uuid::Uuid;
 
 pub fn router(
     db: DatabaseConnection,
     keycloak_auth_instance: Arc<KeycloakAuthInstance>,
     s3: Arc<S3Client>,
+    event_hub: Arc<EventHub>,
 ) -> Router {
-    Router::new()
+    // Browsers cannot set headers on EventSource requests, so the events
+    // stream is authorised by a short-lived ticket from get_events_ticket
+    let events_router = Router::new()
+        .route("/:id/events", routing::get(get_events))
+        .with_state((db.clone(), s3.clone()))
+        .layer(Extension(event_hub.clone()));
+
+    // Any authenticated user can create submissions and see their own, with
+    // their inputs and outputs
+    let user_router = Router::new()
         .route("/", routing::get(get_all).post(create_one))
+        .route("/:id", routing::get(get_one))
+        .route("/:id/inputs", routing::get(get_inputs))
+        .route("/:id/outputs", routing::get(get_outputs))
+        .route("/:id/events/ticket", routing::post(get_events_ticket))
+        .with_state((db.clone(), s3.clone()))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance.clone())
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .build(),
+        );
+
+    Router::new()
         .route(
             "/:id",
-            routing::get(get_one)
-                .put(update_one)
+            routing::put(update_one)
                 .delete(delete_one)
                 .post(execute_workflow),
         )
+        .route("/:id/runs", routing::get(get_runs))
+        .route("/:id/runs/:run_id", routing::delete(cancel_run))
+        .route("/:id/runs/:run_id/events", routing::get(get_run_events))
+        .route("/:id/schedules", routing::get(get_schedules))
+        .route(
+            "/:id/schedules/:schedule_id",
+            routing::put(update_schedule).delete(delete_schedule),
+        )
+        .route("/:id/outputs/*path", routing::get(get_output))
+        .route("/:id/outputs.zip", routing::get(download_outputs_zip))
+        .route("/:id/outputs.tar.gz", routing::get(download_outputs_tar_gz))
         .route("/:id/:filename", routing::get(generate_download_url))
-        .with_state((db, s3))
+        .with_state((db.clone(), s3.clone()))
         .layer(
             KeycloakAuthLayer::<Role>::builder()
-                .instance(keycloak_auth_instance)
+                .instance(keycloak_auth_instance.clone())
                 .passthrough_mode(PassthroughMode::Block)
                 .persist_raw_claims(false)
                 .expected_audiences(vec![String::from("account")])
                 .required_roles(vec![Role::Administrator])
                 .build(),
         )
+        .merge(user_router)
+        .merge(events_router)
+        .merge(crate::uploads::multipart::views::router(
+            db,
+            keycloak_auth_instance,
+            s3,
+            event_hub,
+        ))
 }
 
 const RESOURCE_NAME: &str = "submissions";
@@ -66,10 +115,16 @@ const RESOURCE_NAME: &str = "submissions";
 pub async fn get_all(
     Query(params): Query<FilterOptions>,
     State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
 ) -> impl IntoResponse {
     let (offset, limit) = parse_range(params.range.clone());
 
-    let condition = apply_filters(params.filter.clone(), &[("name", super::db::Column::Name)]);
+    let mut condition = apply_filters(params.filter.clone(), &[("name", super::db::Column::Name)]);
+
+    // Administrators see all submissions, others only their own
+    if !crate::common::auth::is_admin(&token) {
+        condition = condition.add(super::db::Column::CreatedBy.eq(token.subject.clone()));
+    }
 
     let (order_column, order_direction) = generic_sort(
         params.sort.clone(),
@@ -119,6 +174,7 @@ pub async fn get_all(
 )]
 pub async fn create_one(
     State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
     Json(payload): Json<super::models::SubmissionCreate>,
 ) -> Result<(StatusCode, Json<super::models::Submission>), (StatusCode, Json<String>)> {
     let new_obj = super::db::Model {
@@ -129,6 +185,12 @@ pub async fn create_one(
         comment: payload.comment,
         created_on: chrono::Utc::now().naive_utc(),
         last_updated: chrono::Utc::now().naive_utc(),
+        created_by: Some(token.subject),
+        is_pinned: false,
+        last_run_id: 0,
+        retry_max_attempts: 1,
+        retry_backoff_seconds: 300,
+        retry_reasons: None,
     }
     .into_active_model();
 
@@ -167,12 +229,17 @@ pub async fn create_one(
 )]
 pub async fn get_one(
     State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(tracker): Extension<Arc<PodTracker>>,
+    Extension(token): Extension<KeycloakToken<Role>>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<super::models::Submission>, (StatusCode, Json<String>)> {
     let obj = match super::db::Entity::find_by_id(id).one(&db).await {
-        Ok(obj) => obj.unwrap(),
+        Ok(Some(obj)) => obj,
         _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
     };
+    if !super::services::user_can_access(&token, &obj) {
+        return Err((StatusCode::FORBIDDEN, Json("Forbidden".to_string())));
+    }
     let outputs = crate::external::s3::services::get_outputs_from_submission(&s3, &obj)
         .await
         .unwrap();
@@ -180,20 +247,297 @@ pub async fn get_one(
         .await
         .unwrap();
 
-    // let status: Vec<super::run_status::db::Model> = obj
-    //     .find_related(super::run_status::db::Entity)
-    //     .all(&db)
-    //     .await
-    //     .unwrap();
+    let jobs =
+        crate::external::k8s::services::get_jobs_for_submission_id(&tracker, obj.id).unwrap();
+    let metrics: Vec<super::run_metrics::db::Model> = obj
+        .find_related(super::run_metrics::db::Entity)
+        .all(&db)
+        .await
+        .unwrap();
 
-    let jobs = crate::external::k8s::services::get_jobs_for_submission_id(obj.id)
+    let runs: Vec<super::run_status::db::Model> = obj
+        .find_related(super::run_status::db::Entity)
+        .order_by_asc(super::run_status::db::Column::TimeAddedUtc)
+        .all(&db)
         .await
         .unwrap();
-    let submission: super::models::Submission = (obj.clone(), uploads, jobs, outputs).into();
+
+    let mut submission: super::models::Submission = (obj.clone(), uploads, jobs, outputs).into();
+    submission.metrics = metrics.into_iter().map(|metrics| metrics.into()).collect();
+    submission.runs = runs.into_iter().map(|run| run.into()).collect();
 
     Ok(Json(submission))
 }
 
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/runs", RESOURCE_NAME),
+    responses((status = OK, body = Vec<super::run_queue::models::QueuedRun>))
+)]
+pub async fn get_runs(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<super::run_queue::models::QueuedRun>>, (StatusCode, Json<String>)> {
+    // Every attempt of the submission's runs, and how each one ended
+    match super::run_queue::services::get_for_submission(&db, id).await {
+        Ok(runs) => Ok(Json(runs)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to fetch runs".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = format!("/api/{}/{{id}}/runs/{{run_id}}", RESOURCE_NAME),
+    responses((status = OK, body = super::run_queue::models::QueuedRun))
+)]
+pub async fn cancel_run(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((id, run_id)): Path<(Uuid, i64)>,
+) -> Result<Json<super::run_queue::models::QueuedRun>, (StatusCode, Json<String>)> {
+    // Takes a queued run out of the queue, dispatched runs have to be
+    // stopped in the cluster instead
+    match super::run_queue::services::cancel(&db, id, run_id).await {
+        Ok(Some(queued)) if queued.status == super::run_queue::db::QueueStatus::Cancelled => {
+            Ok(Json((queued, None).into()))
+        }
+        Ok(Some(_)) => Err((
+            StatusCode::CONFLICT,
+            Json("Only queued runs can be cancelled".to_string()),
+        )),
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to cancel run".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/runs/{{run_id}}/events", RESOURCE_NAME),
+    responses((status = OK, body = Vec<super::run_events::models::RunEvent>))
+)]
+pub async fn get_run_events(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((id, run_id)): Path<(Uuid, i64)>,
+) -> Result<Json<Vec<super::run_events::models::RunEvent>>, (StatusCode, Json<String>)> {
+    match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Server error".to_string()),
+            ))
+        }
+    };
+
+    // Served from the events persisted by the reconciler, without calling
+    // the cluster
+    match super::run_events::db::Entity::find()
+        .filter(super::run_events::db::Column::SubmissionId.eq(id))
+        .filter(super::run_events::db::Column::RunId.eq(run_id))
+        .order_by_asc(super::run_events::db::Column::FirstSeen)
+        .all(&db)
+        .await
+    {
+        Ok(events) => Ok(Json(events.into_iter().map(|event| event.into()).collect())),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to fetch events".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/inputs", RESOURCE_NAME),
+    responses((status = OK, body = Vec<crate::uploads::models::UploadTreeNode>))
+)]
+pub async fn get_inputs(
+    Query(params): Query<super::models::InputListOptions>,
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    let obj = match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(obj)) => obj,
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+    if !super::services::user_can_access(&token, &obj) {
+        return Err((StatusCode::FORBIDDEN, Json("Forbidden".to_string())));
+    }
+
+    let uploads: Vec<crate::uploads::models::UploadRead> =
+        super::services::get_input_objects(obj, &db)
+            .await
+            .map_err(|_| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json("Failed to fetch uploads".to_string()),
+                )
+            })?
+            .into_iter()
+            .map(|upload| upload.into())
+            .collect();
+
+    if params.tree.unwrap_or(false) {
+        let tree = crate::uploads::models::UploadTreeNode::build_tree(uploads);
+        Ok(Json(tree).into_response())
+    } else {
+        Ok(Json(uploads).into_response())
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = format!("/api/{}/{{id}}/events/ticket", RESOURCE_NAME),
+    responses((status = CREATED, body = super::events::models::EventsTicket))
+)]
+pub async fn get_events_ticket(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path(id): Path<Uuid>,
+) -> Result<(StatusCode, Json<super::events::models::EventsTicket>), (StatusCode, Json<String>)> {
+    let obj = match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(obj)) => obj,
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+    if !super::services::user_can_access(&token, &obj) {
+        return Err((StatusCode::FORBIDDEN, Json("Forbidden".to_string())));
+    }
+
+    match super::events::services::create_ticket(id) {
+        Ok(ticket) => Ok((
+            StatusCode::CREATED,
+            Json(super::events::models::EventsTicket {
+                ticket,
+                expires_in_seconds: super::events::services::TICKET_EXPIRY_SECONDS,
+            }),
+        )),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to create ticket".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/outputs", RESOURCE_NAME),
+    responses((status = OK, body = Vec<crate::external::s3::models::OutputObjectResponse>))
+)]
+pub async fn get_outputs(
+    Query(params): Query<FilterOptions>,
+    Query(options): Query<super::models::OutputListOptions>,
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<String>)> {
+    let obj = match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(obj)) => obj,
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+    if !super::services::user_can_access(&token, &obj) {
+        return Err((StatusCode::FORBIDDEN, Json("Forbidden".to_string())));
+    }
+
+    let (objects, directories) = crate::external::s3::services::list_outputs(
+        &s3,
+        obj.id,
+        options.prefix.as_deref(),
+        options.delimiter.as_deref(),
+    )
+    .await
+    .map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to list outputs".to_string()),
+        )
+    })?;
+
+    let mut outputs: Vec<crate::external::s3::models::OutputObjectResponse> = directories
+        .into_iter()
+        .map(crate::external::s3::models::OutputObjectResponse::directory)
+        .chain(objects.into_iter().map(|object| object.into()))
+        .collect();
+    for output in outputs.iter_mut().filter(|output| !output.is_directory) {
+        output.url = Some(super::models::output_url(obj.id, &output.path));
+    }
+
+    // Free-text search on the path, as the listing is not from the database
+    let filters: HashMap<String, String> = params
+        .filter
+        .and_then(|filter| serde_json::from_str(&filter).ok())
+        .unwrap_or_default();
+    if let Some(q) = filters.get("q") {
+        let q = q.to_lowercase();
+        outputs.retain(|output| output.path.to_lowercase().contains(&q));
+    }
+
+    let (sort_column, order_direction) = parse_sort(params.sort);
+    outputs.sort_by(|a, b| {
+        let ordering = match sort_column.as_str() {
+            "filename" => a.filename.cmp(&b.filename),
+            "size_bytes" => a.size_bytes.cmp(&b.size_bytes),
+            "last_modified" => a.last_modified.cmp(&b.last_modified),
+            _ => a.path.cmp(&b.path),
+        };
+        match order_direction {
+            Order::Desc => ordering.reverse(),
+            _ => ordering,
+        }
+    });
+
+    let (offset, limit) = parse_range(params.range);
+    let total_count = outputs.len() as u64;
+    let outputs: Vec<crate::external::s3::models::OutputObjectResponse> = outputs
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect();
+
+    let headers = calculate_content_range(offset, limit, total_count, "outputs");
+
+    Ok((headers, Json(outputs)))
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/events", RESOURCE_NAME),
+    responses((status = OK, body = super::events::models::SubmissionEvent, content_type = "text/event-stream"))
+)]
+pub async fn get_events(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<super::events::models::EventsOptions>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, Json<String>)> {
+    if super::events::services::validate_ticket(&options.ticket, id).is_err() {
+        return Err((StatusCode::UNAUTHORIZED, Json("Invalid ticket".to_string())));
+    }
+    match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(_)) => {}
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+
+    // Stream the events of this submission. Slow clients that lag behind skip
+    // the missed events, the next progress event supersedes them anyway.
+    let stream = BroadcastStream::new(hub.subscribe()).filter_map(move |event| match event {
+        Ok(event) if event.submission_id() == id => Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .ok()
+            .map(Ok),
+        _ => None,
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 #[utoipa::path(
     put,
     path = format!("/api/{}/{{id}}", RESOURCE_NAME),
@@ -203,7 +547,11 @@ pub async fn update_one(
     State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
     Path(id): Path<Uuid>,
     Json(payload): Json<super::models::SubmissionUpdate>,
-) -> impl IntoResponse {
+) -> Result<Json<super::models::Submission>, (StatusCode, Json<String>)> {
+    if let Err(e) = payload.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(e)));
+    }
+
     let obj: super::db::ActiveModel = super::db::Entity::find_by_id(id)
         .one(&db)
         .await
@@ -217,7 +565,7 @@ pub async fn update_one(
 
     let response_obj: super::models::Submission = obj.into();
 
-    Json(response_obj)
+    Ok(Json(response_obj))
 }
 
 // Delete one
@@ -267,130 +615,373 @@ pub async fn delete_one(
     StatusCode::NO_CONTENT
 }
 
-#[debug_handler]
+#[utoipa::path(
+    post,
+    path = format!("/api/{}/{{id}}", RESOURCE_NAME),
+    responses(
+        (status = ACCEPTED, body = super::run_queue::models::QueuedRun),
+        (status = CREATED, body = super::run_schedules::models::ScheduledRun),
+    )
+)]
 pub async fn execute_workflow(
+    Query(options): Query<super::run_queue::models::ExecuteOptions>,
     State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
     Path(id): Path<Uuid>,
-) -> StatusCode {
-    let config = crate::config::Config::from_env();
+) -> Result<Response, (StatusCode, Json<String>)> {
+    match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Server error".to_string()),
+            ))
+        }
+    };
 
-    // Generate a unique job name
-    let random_number: u32 = rand::thread_rng().gen_range(10000..99999);
-    let job_name = format!("{}-{}-{}", config.pod_prefix, id, random_number);
+    // Deferred or recurring runs are queued by the scheduler once due
+    if options.not_before.is_some() || options.schedule.is_some() {
+        let schedule = match options.schedule.as_deref() {
+            Some(schedule) => match super::run_schedules::services::parse_schedule(schedule) {
+                Ok(schedule) => Some(schedule),
+                Err(e) => return Err((StatusCode::BAD_REQUEST, Json(e.to_string()))),
+            },
+            None => None,
+        };
+        return match super::run_schedules::services::create(
+            &db,
+            id,
+            options.not_before.map(|not_before| not_before.naive_utc()),
+            schedule,
+            options.priority.unwrap_or(0),
+            &token.subject,
+            &token.extra.profile.preferred_username,
+        )
+        .await
+        {
+            Ok(scheduled) => Ok((
+                StatusCode::CREATED,
+                Json(super::run_schedules::models::ScheduledRun::from(scheduled)),
+            )
+                .into_response()),
+            Err(_) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to schedule run".to_string()),
+            )),
+        };
+    }
 
-    // Fetch submission and related uploads
-    let obj = match super::db::Entity::find_by_id(id).one(&db).await {
-        Ok(Some(submission)) => submission,
-        _ => return StatusCode::NOT_FOUND,
+    // The dispatcher submits the run once the concurrency limits allow it
+    let queued = match super::run_queue::services::enqueue(
+        &db,
+        id,
+        options.priority.unwrap_or(0),
+        &token.subject,
+        &token.extra.profile.preferred_username,
+    )
+    .await
+    {
+        Ok(queued) => queued,
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to queue run".to_string()),
+            ))
+        }
     };
+    let position = super::run_queue::services::position(&db, &queued)
+        .await
+        .unwrap_or(None);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(super::run_queue::models::QueuedRun::from((
+            queued, position,
+        ))),
+    )
+        .into_response())
+}
 
-    let input_object_ids: Vec<Uuid> = obj
-        .find_related(crate::uploads::db::Entity)
-        .all(&db)
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/schedules", RESOURCE_NAME),
+    responses((status = OK, body = Vec<super::run_schedules::models::ScheduledRun>))
+)]
+pub async fn get_schedules(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<super::run_schedules::models::ScheduledRun>>, (StatusCode, Json<String>)> {
+    match super::run_schedules::services::get_for_submission(&db, id).await {
+        Ok(schedules) => Ok(Json(schedules)),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to fetch schedules".to_string()),
+        )),
+    }
+}
+
+async fn find_schedule(
+    db: &DatabaseConnection,
+    id: Uuid,
+    schedule_id: Uuid,
+) -> Result<super::run_schedules::db::Model, (StatusCode, Json<String>)> {
+    match super::run_schedules::db::Entity::find_by_id(schedule_id)
+        .filter(super::run_schedules::db::Column::SubmissionId.eq(id))
+        .one(db)
         .await
-        .unwrap()
-        .into_iter()
-        .map(|assoc| assoc.id)
-        .collect();
+    {
+        Ok(Some(scheduled)) => Ok(scheduled),
+        Ok(None) => Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Server error".to_string()),
+        )),
+    }
+}
 
-    // Set up Kubernetes client and configuration
-    let config = crate::config::Config::from_env();
-    let client = match crate::external::k8s::services::refresh_token_and_get_client().await {
-        Ok(client) => client,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
-    };
+#[utoipa::path(
+    put,
+    path = format!("/api/{}/{{id}}/schedules/{{schedule_id}}", RESOURCE_NAME),
+    responses((status = OK, body = super::run_schedules::models::ScheduledRun))
+)]
+pub async fn update_schedule(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((id, schedule_id)): Path<(Uuid, Uuid)>,
+    Json(mut payload): Json<super::run_schedules::models::ScheduleUpdate>,
+) -> Result<Json<super::run_schedules::models::ScheduledRun>, (StatusCode, Json<String>)> {
+    let scheduled = find_schedule(&db, id, schedule_id).await?;
+
+    if let Some(Some(schedule)) = &payload.schedule {
+        match super::run_schedules::services::parse_schedule(schedule) {
+            Ok(schedule) => payload.schedule = Some(Some(schedule)),
+            Err(e) => return Err((StatusCode::BAD_REQUEST, Json(e.to_string()))),
+        }
+    }
 
-    let base_image = format!(
-        "{}:{}",
-        config.submission_base_image, config.submission_base_image_tag,
-    );
-    // Create a new TrainingWorkload custom resource
-    let training_workload = TrainingWorkload::new(
-        &job_name,
-        TrainingWorkloadSpec {
-            allow_privilege_escalation: Some(ValueField { value: true }),
-            environment: Environment {
-                items: EnvironmentItems {
-                    input_object_ids: ValueField {
-                        value: serde_json::to_string(&input_object_ids).unwrap(),
-                    },
-                    s3_access_key: ValueField {
-                        value: config.s3_access_key.to_string(),
-                    },
-                    s3_bucket_id: ValueField {
-                        value: config.s3_bucket.to_string(),
-                    },
-                    s3_prefix: ValueField {
-                        value: config.s3_prefix.to_string(),
-                    },
-                    s3_secret_key: ValueField {
-                        value: config.s3_secret_key.to_string(),
-                    },
-                    s3_url: ValueField {
-                        value: config.s3_url.to_string(),
-                    },
-                    submission_id: ValueField {
-                        value: id.to_string(),
-                    },
-                    base_image: ValueField {
-                        value: base_image.clone(),
-                    },
-                },
-            },
-            gpu: ValueField {
-                value: "1".to_string(),
-            },
-            image: ValueField { value: base_image },
-            image_pull_policy: ValueField {
-                value: "Always".to_string(),
-            },
-            name: ValueField {
-                value: job_name.clone(),
-            },
-            run_as_gid: None,
-            run_as_uid: None,
-            run_as_user: None,
-            service_type: None,
-            usage: Some("Submit".to_string()),
-        },
-    );
+    match super::run_schedules::services::reschedule(&db, scheduled, payload).await {
+        Ok(scheduled) => Ok(Json(scheduled.into())),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to reschedule run".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = format!("/api/{}/{{id}}/schedules/{{schedule_id}}", RESOURCE_NAME),
+    responses((status = NO_CONTENT))
+)]
+pub async fn delete_schedule(
+    State((db, _s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((id, schedule_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<String>)> {
+    // Runs the schedule has already queued are left to finish
+    let scheduled = find_schedule(&db, id, schedule_id).await?;
+
+    match scheduled.delete(&db).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to cancel scheduled run".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/outputs/{{path}}", RESOURCE_NAME),
+    responses((status = OK, body = super::models::DownloadPath))
+)]
+pub async fn get_output(
+    state: State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((submission_id, path)): Path<(Uuid, String)>,
+    options: Query<super::previews::models::PreviewOptions>,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    // Signed download link of an output, the path may include subdirectories.
+    // The wildcard has to end the route, so {path}/preview is told apart here
+    match path.strip_suffix("/preview") {
+        Some(path) => {
+            Ok(
+                get_output_preview(state, Path((submission_id, path.to_string())), options)
+                    .await?
+                    .into_response(),
+            )
+        }
+        None => Ok(generate_download_url(state, Path((submission_id, path)))
+            .await?
+            .into_response()),
+    }
+}
 
-    // Submit the custom resource to Kubernetes
-    let api: Api<TrainingWorkload> = Api::namespaced(client, &config.kube_namespace);
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/outputs/{{path}}/preview", RESOURCE_NAME),
+    responses((status = OK, body = super::previews::models::OutputPreview))
+)]
+pub async fn get_output_preview(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((submission_id, path)): Path<(Uuid, String)>,
+    Query(options): Query<super::previews::models::PreviewOptions>,
+) -> Result<Json<super::previews::models::OutputPreview>, (StatusCode, Json<String>)> {
+    if !matches!(
+        super::db::Entity::find_by_id(submission_id).one(&db).await,
+        Ok(Some(_))
+    ) {
+        return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string())));
+    }
 
-    match api.create(&PostParams::default(), &training_workload).await {
-        Ok(_) => StatusCode::CREATED,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    let path = match super::services::normalise_output_path(&path) {
+        Some(path) => path,
+        None => return Err((StatusCode::BAD_REQUEST, Json("Invalid path".to_string()))),
+    };
+    let kind = match super::previews::models::PreviewKind::from_path(&path) {
+        Some(kind) => kind,
+        None => {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                Json("No preview available for this file type".to_string()),
+            ))
+        }
+    };
+    let lines = options
+        .lines
+        .unwrap_or(super::previews::services::PREVIEW_DEFAULT_LINES)
+        .min(super::previews::services::PREVIEW_MAX_LINES);
+
+    match super::previews::services::get_preview(&s3, submission_id, &path, kind, lines).await {
+        Ok(preview) => Ok(Json(preview)),
+        Err(e) => match e.downcast_ref::<aws_sdk_s3::error::SdkError<GetObjectError>>() {
+            Some(err) if matches!(err.as_service_error(), Some(GetObjectError::NoSuchKey(_))) => {
+                Err((StatusCode::NOT_FOUND, Json("Not Found".to_string())))
+            }
+            Some(_) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to read output".to_string()),
+            )),
+            None => Err((StatusCode::UNPROCESSABLE_ENTITY, Json(e.to_string()))),
+        },
     }
 }
 
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/{{filename}}", RESOURCE_NAME),
+    responses((status = OK, body = super::models::DownloadPath))
+)]
 pub async fn generate_download_url(
-    Path((submission_id, filename)): Path<(Uuid, String)>,
-) -> Result<Json<super::models::DownloadPath>, (StatusCode, String)> {
-    // Returns a presigned URL from S3. Assumes the client has access to the
-    // S3 domain (EPFL network in this case).
-    let config = crate::config::Config::from_env();
-    let s3 = crate::external::s3::services::get_client(&config).await;
-
-    let key = format!(
-        "{}/outputs/{}/{}",
-        config.s3_prefix, submission_id, filename
-    );
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path((submission_id, path)): Path<(Uuid, String)>,
+) -> Result<Json<super::models::DownloadPath>, (StatusCode, Json<String>)> {
+    // Returns a signed link to the API's download proxy, which can be shared
+    // with users without an account or access to the S3 domain until it
+    // expires. The path is relative to the submission's outputs and may
+    // include subdirectories.
+    if !matches!(
+        super::db::Entity::find_by_id(submission_id).one(&db).await,
+        Ok(Some(_))
+    ) {
+        return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string())));
+    }
 
-    // Get presigned URL to give to client
-    let presigned_request = s3
-        .get_object()
-        .bucket(&config.s3_bucket)
-        .key(&key)
-        .presigned(
-            PresigningConfig::builder()
-                .expires_in(Duration::from_secs(60 * 60)) // One hour
-                .build()
-                .expect("Duration is invalid"),
-        )
+    let path = match super::services::normalise_output_path(&path) {
+        Some(path) => path,
+        None => return Err((StatusCode::BAD_REQUEST, Json("Invalid path".to_string()))),
+    };
+    match crate::external::s3::services::output_exists(&s3, submission_id, &path).await {
+        Ok(true) => {}
+        Ok(false) => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to check output".to_string()),
+            ))
+        }
+    }
+
+    match super::services::create_download_token(submission_id, &path) {
+        Ok(token) => Ok(Json(super::models::DownloadPath {
+            url: format!("/api/downloads/{}", token),
+        })),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to create download link".to_string()),
+        )),
+    }
+}
+
+async fn download_outputs(
+    db: DatabaseConnection,
+    s3: Arc<S3Client>,
+    id: Uuid,
+    options: super::models::ArchiveOptions,
+    format: ArchiveFormat,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    let obj = match super::db::Entity::find_by_id(id).one(&db).await {
+        Ok(Some(obj)) => obj,
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+    let pattern = match options.glob.as_deref().map(glob::Pattern::new) {
+        Some(Ok(pattern)) => Some(pattern),
+        Some(Err(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json("Invalid glob pattern".to_string()),
+            ))
+        }
+        None => None,
+    };
+
+    let outputs = crate::external::s3::services::get_outputs_from_submission(&s3, &obj)
         .await
-        .unwrap();
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to list outputs".to_string()),
+            )
+        })?;
+
+    let stream =
+        crate::external::s3::services::stream_outputs_archive(s3, obj.id, outputs, pattern, format);
+    let disposition = format!(
+        "attachment; filename=\"{}-outputs.{}\"",
+        obj.id,
+        format.extension()
+    );
 
-    let presigned_url = presigned_request.uri().to_string();
-    Ok(Json(super::models::DownloadPath { url: presigned_url }))
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/outputs.zip", RESOURCE_NAME),
+    responses((status = OK, content_type = "application/zip"))
+)]
+pub async fn download_outputs_zip(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<super::models::ArchiveOptions>,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    download_outputs(db, s3, id, options, ArchiveFormat::Zip).await
+}
+
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/outputs.tar.gz", RESOURCE_NAME),
+    responses((status = OK, content_type = "application/gzip"))
+)]
+pub async fn download_outputs_tar_gz(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<super::models::ArchiveOptions>,
+) -> Result<Response, (StatusCode, Json<String>)> {
+    download_outputs(db, s3, id, options, ArchiveFormat::TarGz).await
 }
@@ -8,6 +8,7 @@ pub struct Model {
     pub iterator: i32,
     pub input_object_id: Uuid,
     pub submission_id: Uuid,
+    pub relative_path: String, // Unique within the submission
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -9,10 +9,13 @@ pub struct Model {
     pub id: Uuid,
     pub created_on: NaiveDateTime,
     pub filename: String,
+    pub relative_path: String,
     pub size_bytes: i64,
+    pub bytes_received: i64,
     pub all_parts_received: bool,
     pub last_part_received: Option<NaiveDateTime>,
     pub processing_message: Option<String>,
+    pub multipart_upload_id: Option<String>, // Set while an S3 multipart upload is in progress
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,5 +1,7 @@
 pub mod associations;
 pub mod db;
 pub mod models;
+pub mod multipart;
+pub mod partials;
 pub mod services;
 pub mod views;
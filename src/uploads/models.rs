@@ -9,7 +9,9 @@ pub struct UploadRead {
     pub id: Uuid,
     created_on: NaiveDateTime,
     filename: String,
+    relative_path: String,
     size_bytes: i64,
+    bytes_received: i64,
     all_parts_received: bool,
     last_part_received: Option<NaiveDateTime>,
     processing_message: Option<String>,
@@ -21,10 +23,123 @@ impl From<super::db::Model> for UploadRead {
             id: model.id,
             created_on: model.created_on,
             filename: model.filename,
+            relative_path: model.relative_path,
             size_bytes: model.size_bytes,
+            bytes_received: model.bytes_received,
             all_parts_received: model.all_parts_received,
             last_part_received: model.last_part_received,
             processing_message: model.processing_message,
         }
     }
 }
+
+#[derive(ToSchema, Serialize, Debug)]
+pub struct UploadTreeNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool, // A file and a folder may share a path, ie. `a` and `a/b`
+    pub children: Vec<UploadTreeNode>,
+    pub upload: Option<UploadRead>,
+}
+
+impl UploadTreeNode {
+    fn new(name: &str, path: String, is_dir: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            path,
+            is_dir,
+            children: vec![],
+            upload: None,
+        }
+    }
+
+    /// Build a directory tree from a flat list of uploads using their
+    /// relative paths, ie. `pod5_pass/barcode01/reads.pod5`
+    pub fn build_tree(uploads: Vec<UploadRead>) -> Vec<UploadTreeNode> {
+        let mut root = UploadTreeNode::new("", String::new(), true);
+
+        for upload in uploads {
+            let segments: Vec<&str> = upload.relative_path.split('/').collect();
+            let mut node = &mut root;
+
+            for (i, segment) in segments.iter().enumerate() {
+                let path = segments[..=i].join("/");
+                let is_dir = i + 1 < segments.len();
+                let position = match node
+                    .children
+                    .iter()
+                    .position(|child| child.path == path && child.is_dir == is_dir)
+                {
+                    Some(position) => position,
+                    None => {
+                        node.children
+                            .push(UploadTreeNode::new(segment, path, is_dir));
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[position];
+            }
+            node.upload = Some(upload);
+        }
+
+        root.sort();
+        root.children
+    }
+
+    fn sort(&mut self) {
+        self.children
+            .sort_by(|a, b| a.name.cmp(&b.name).then(b.is_dir.cmp(&a.is_dir)));
+        for child in self.children.iter_mut() {
+            child.sort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(relative_path: &str) -> UploadRead {
+        UploadRead {
+            id: Uuid::new_v4(),
+            created_on: chrono::Utc::now().naive_utc(),
+            filename: relative_path.rsplit('/').next().unwrap().to_string(),
+            relative_path: relative_path.to_string(),
+            size_bytes: 0,
+            bytes_received: 0,
+            all_parts_received: false,
+            last_part_received: None,
+            processing_message: None,
+        }
+    }
+
+    #[test]
+    fn nests_uploads_by_folder() {
+        let tree = UploadTreeNode::build_tree(vec![
+            upload("pod5_pass/barcode02/reads.pod5"),
+            upload("pod5_pass/barcode01/reads.pod5"),
+            upload("reads.pod5"),
+        ]);
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].name, "pod5_pass");
+        assert!(tree[0].is_dir && tree[0].upload.is_none());
+        let barcodes: Vec<&str> = tree[0].children.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(barcodes, ["pod5_pass/barcode01", "pod5_pass/barcode02"]);
+        assert_eq!(
+            tree[0].children[0].children[0].path,
+            "pod5_pass/barcode01/reads.pod5"
+        );
+        assert!(!tree[1].is_dir && tree[1].upload.is_some());
+    }
+
+    #[test]
+    fn separates_files_from_folders_with_the_same_path() {
+        let tree = UploadTreeNode::build_tree(vec![upload("a/b"), upload("a")]);
+
+        assert_eq!(tree.len(), 2);
+        assert!(tree[0].is_dir && tree[0].upload.is_none());
+        assert_eq!(tree[0].children[0].path, "a/b");
+        assert!(!tree[1].is_dir && tree[1].upload.is_some());
+    }
+}
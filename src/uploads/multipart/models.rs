@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(ToSchema, Deserialize)]
+pub struct MultipartUploadCreate {
+    pub filename: String,
+    pub relative_path: Option<String>,
+    pub size_bytes: i64,
+}
+
+#[derive(ToSchema, Serialize)]
+pub struct PresignedPart {
+    pub part_number: i32,
+    pub url: String,
+    pub size_bytes: i64,
+}
+
+#[derive(ToSchema, Serialize)]
+pub struct MultipartUpload {
+    pub id: Uuid,
+    pub part_size_bytes: i64,
+    pub parts: Vec<PresignedPart>,
+}
+
+#[derive(ToSchema, Deserialize)]
+pub struct CompletedPart {
+    pub part_number: i32,
+    pub etag: String,
+}
+
+#[derive(ToSchema, Deserialize)]
+pub struct MultipartUploadComplete {
+    pub parts: Vec<CompletedPart>,
+}
@@ -0,0 +1,129 @@
+use super::models::{CompletedPart, PresignedPart};
+use crate::config::Config;
+use anyhow::{Error, Result};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart as S3CompletedPart};
+use aws_sdk_s3::Client as S3Client;
+use std::time::Duration;
+use uuid::Uuid;
+
+// S3 allows at most 10000 parts per upload, of at least 5 MiB except the last
+const DEFAULT_PART_SIZE_BYTES: i64 = 64 * 1024 * 1024;
+const MAX_PARTS: i64 = 10000;
+
+pub fn part_size(size_bytes: i64) -> i64 {
+    // Grow the part size for large files to stay within the part limit
+    let min_part_size = (size_bytes + MAX_PARTS - 1) / MAX_PARTS;
+    DEFAULT_PART_SIZE_BYTES.max(min_part_size)
+}
+
+fn object_key(config: &Config, id: Uuid) -> String {
+    format!("{}/{}", config.s3_prefix, id)
+}
+
+pub async fn initiate(s3: &S3Client, id: Uuid) -> Result<String, Error> {
+    let config = Config::from_env();
+    let response = s3
+        .create_multipart_upload()
+        .bucket(&config.s3_bucket)
+        .key(object_key(&config, id))
+        .content_type("application/octet-stream")
+        .send()
+        .await?;
+
+    response
+        .upload_id
+        .ok_or_else(|| anyhow::anyhow!("S3 did not return a multipart upload ID"))
+}
+
+pub async fn abort(s3: &S3Client, id: Uuid, multipart_upload_id: &str) -> Result<(), Error> {
+    // Discard the parts received so far, which S3 would otherwise keep
+    let config = Config::from_env();
+    s3.abort_multipart_upload()
+        .bucket(&config.s3_bucket)
+        .key(object_key(&config, id))
+        .upload_id(multipart_upload_id)
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+pub async fn presign_parts(
+    s3: &S3Client,
+    id: Uuid,
+    multipart_upload_id: &str,
+    size_bytes: i64,
+) -> Result<Vec<PresignedPart>, Error> {
+    // Presign a PUT URL for each part so the client uploads directly to S3.
+    // An empty file is still uploaded as a single (empty) part.
+    let config = Config::from_env();
+    let part_size = part_size(size_bytes);
+    let part_count = ((size_bytes + part_size - 1) / part_size).max(1);
+
+    let mut parts = Vec::new();
+    for index in 0..part_count {
+        let part_number = (index + 1) as i32;
+        let presigned_request = s3
+            .upload_part()
+            .bucket(&config.s3_bucket)
+            .key(object_key(&config, id))
+            .upload_id(multipart_upload_id)
+            .part_number(part_number)
+            .presigned(
+                PresigningConfig::builder()
+                    .expires_in(Duration::from_secs(6 * 60 * 60)) // Six hours
+                    .build()?,
+            )
+            .await?;
+
+        parts.push(PresignedPart {
+            part_number,
+            url: presigned_request.uri().to_string(),
+            size_bytes: part_size.min(size_bytes - index * part_size),
+        });
+    }
+
+    Ok(parts)
+}
+
+pub async fn complete(
+    s3: &S3Client,
+    id: Uuid,
+    multipart_upload_id: &str,
+    mut parts: Vec<CompletedPart>,
+) -> Result<i64, Error> {
+    // Finalise the upload and return the size of the resulting object
+    let config = Config::from_env();
+    parts.sort_by_key(|part| part.part_number);
+    let completed_parts = parts
+        .into_iter()
+        .map(|part| {
+            S3CompletedPart::builder()
+                .part_number(part.part_number)
+                .e_tag(part.etag)
+                .build()
+        })
+        .collect();
+
+    s3.complete_multipart_upload()
+        .bucket(&config.s3_bucket)
+        .key(object_key(&config, id))
+        .upload_id(multipart_upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(completed_parts))
+                .build(),
+        )
+        .send()
+        .await?;
+
+    let head = s3
+        .head_object()
+        .bucket(&config.s3_bucket)
+        .key(object_key(&config, id))
+        .send()
+        .await?;
+
+    Ok(head.content_length.unwrap_or(0))
+}
@@ -0,0 +1,327 @@
+use crate::common::auth::Role;
+use crate::submissions::events::models::{SubmissionEvent, UploadEvent, UploadState};
+use crate::submissions::events::services::EventHub;
+use crate::uploads::{associations, db, services};
+use aws_sdk_s3::Client as S3Client;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing, Extension, Json, Router,
+};
+use axum_keycloak_auth::{
+    decode::KeycloakToken, instance::KeycloakAuthInstance, layer::KeycloakAuthLayer,
+    PassthroughMode,
+};
+use chrono::Utc;
+use sea_orm::{query::*, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, Set};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn router(
+    db: DatabaseConnection,
+    keycloak_auth_instance: Arc<KeycloakAuthInstance>,
+    s3: Arc<S3Client>,
+    event_hub: Arc<EventHub>,
+) -> Router {
+    // Uploads made through the API rather than tusd, for clients that cannot
+    // reach the tusd deployment. Parts are sent directly to S3. Any
+    // authenticated user may upload to the submissions they have access to.
+    Router::new()
+        .route("/:id/uploads", routing::post(create_one))
+        .route("/:id/uploads/:upload_id", routing::delete(delete_one))
+        .route(
+            "/:id/uploads/:upload_id/complete",
+            routing::post(complete_one),
+        )
+        .with_state((db, s3))
+        .layer(Extension(event_hub))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance)
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .build(),
+        )
+}
+
+async fn check_submission_access(
+    db: &DatabaseConnection,
+    token: &KeycloakToken<Role>,
+    submission_id: Uuid,
+) -> Result<(), (StatusCode, Json<String>)> {
+    let submission = match crate::submissions::db::Entity::find_by_id(submission_id)
+        .one(db)
+        .await
+    {
+        Ok(Some(obj)) => obj,
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    };
+    if !crate::submissions::services::user_can_access(token, &submission) {
+        return Err((StatusCode::FORBIDDEN, Json("Forbidden".to_string())));
+    }
+
+    Ok(())
+}
+
+async fn find_submission_upload(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+    upload_id: Uuid,
+) -> Result<db::Model, (StatusCode, Json<String>)> {
+    // Only return the upload if it belongs to the given submission
+    let association = associations::db::Entity::find()
+        .filter(associations::db::Column::SubmissionId.eq(submission_id))
+        .filter(associations::db::Column::InputObjectId.eq(upload_id))
+        .one(db)
+        .await;
+
+    match association {
+        Ok(Some(_)) => {}
+        _ => return Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    }
+
+    match db::Entity::find_by_id(upload_id).one(db).await {
+        Ok(Some(obj)) => Ok(obj),
+        _ => Err((StatusCode::NOT_FOUND, Json("Not Found".to_string()))),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/submissions/{id}/uploads",
+    responses((status = CREATED, body = super::models::MultipartUpload))
+)]
+pub async fn create_one(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path(submission_id): Path<Uuid>,
+    Json(payload): Json<super::models::MultipartUploadCreate>,
+) -> Result<(StatusCode, Json<super::models::MultipartUpload>), (StatusCode, Json<String>)> {
+    check_submission_access(&db, &token, submission_id).await?;
+
+    if payload.size_bytes < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json("File size must not be negative".to_string()),
+        ));
+    }
+    if !services::has_allowed_extension(&payload.filename) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json("File extension not allowed".to_string()),
+        ));
+    }
+    let relative_path =
+        services::normalise_relative_path(payload.relative_path.as_deref(), &payload.filename)
+            .map_err(|e| (StatusCode::BAD_REQUEST, Json(e.to_string())))?;
+
+    match services::free_upload_path(&db, &s3, submission_id, &relative_path).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json("File already uploaded with this path in this submission".to_string()),
+            ))
+        }
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to check existing uploads".to_string()),
+            ))
+        }
+    }
+
+    match crate::quota::services::get_quota(&db, &token.subject).await {
+        Ok(quota) if quota.allows(payload.size_bytes) => {}
+        Ok(_) => {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json("Upload exceeds the storage quota".to_string()),
+            ))
+        }
+        Err(_) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to check storage quota".to_string()),
+            ))
+        }
+    }
+
+    let object = services::create_object(
+        &db,
+        submission_id,
+        payload.filename,
+        relative_path,
+        payload.size_bytes,
+    )
+    .await
+    .map_err(|e| match services::is_path_conflict(&e) {
+        true => (
+            StatusCode::CONFLICT,
+            Json("File already uploaded with this path in this submission".to_string()),
+        ),
+        false => (StatusCode::INTERNAL_SERVER_ERROR, Json(e.to_string())),
+    })?;
+
+    // Start the S3 upload, removing the object again if S3 refuses it
+    let multipart_upload_id = match super::services::initiate(&s3, object.id).await {
+        Ok(multipart_upload_id) => multipart_upload_id,
+        Err(_) => {
+            let _ = services::delete_object(&db, &s3, object.id).await;
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to start upload".to_string()),
+            ));
+        }
+    };
+
+    let object_id = object.id;
+    let mut obj: db::ActiveModel = object.into();
+    obj.multipart_upload_id = Set(Some(multipart_upload_id.clone()));
+    let object = match obj.update(&db).await {
+        Ok(object) => object,
+        Err(_) => {
+            let _ = super::services::abort(&s3, object_id, &multipart_upload_id).await;
+            let _ = services::delete_object(&db, &s3, object_id).await;
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to update upload".to_string()),
+            ));
+        }
+    };
+
+    // Without the part URLs the upload cannot proceed, so it is aborted
+    let parts = match super::services::presign_parts(
+        &s3,
+        object.id,
+        &multipart_upload_id,
+        object.size_bytes,
+    )
+    .await
+    {
+        Ok(parts) => parts,
+        Err(_) => {
+            let _ = services::delete_object(&db, &s3, object.id).await;
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Failed to presign upload parts".to_string()),
+            ));
+        }
+    };
+
+    hub.publish(SubmissionEvent::Upload(UploadEvent::from_upload(
+        submission_id,
+        &object,
+        UploadState::Created,
+    )));
+
+    Ok((
+        StatusCode::CREATED,
+        Json(super::models::MultipartUpload {
+            id: object.id,
+            part_size_bytes: super::services::part_size(object.size_bytes),
+            parts,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/submissions/{id}/uploads/{upload_id}/complete",
+    responses((status = OK, body = crate::uploads::models::UploadRead))
+)]
+pub async fn complete_one(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path((submission_id, upload_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<super::models::MultipartUploadComplete>,
+) -> Result<Json<crate::uploads::models::UploadRead>, (StatusCode, Json<String>)> {
+    check_submission_access(&db, &token, submission_id).await?;
+    let object = find_submission_upload(&db, submission_id, upload_id).await?;
+    let multipart_upload_id = match object.multipart_upload_id.clone() {
+        Some(multipart_upload_id) => multipart_upload_id,
+        None => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json("Upload is not in progress".to_string()),
+            ))
+        }
+    };
+
+    let size_bytes = super::services::complete(&s3, object.id, &multipart_upload_id, payload.parts)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json("Failed to complete upload".to_string()),
+            )
+        })?;
+
+    // The quota was checked against the declared size, nothing stops a client
+    // from putting more into the part URLs
+    if size_bytes > object.size_bytes {
+        let _ = services::delete_object(&db, &s3, object.id).await;
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json("Upload is larger than its declared size".to_string()),
+        ));
+    }
+
+    let mut obj: db::ActiveModel = object.into();
+    obj.processing_message = Set(Some("Upload completed".to_owned()));
+    obj.all_parts_received = Set(true);
+    obj.size_bytes = Set(size_bytes);
+    obj.bytes_received = Set(size_bytes);
+    obj.last_part_received = Set(Some(Utc::now().naive_utc()));
+    obj.multipart_upload_id = Set(None);
+
+    match obj.update(&db).await {
+        Ok(object) => {
+            hub.publish(SubmissionEvent::Upload(UploadEvent::from_upload(
+                submission_id,
+                &object,
+                UploadState::Completed,
+            )));
+            Ok(Json(object.into()))
+        }
+        _ => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to update upload".to_string()),
+        )),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/submissions/{id}/uploads/{upload_id}",
+    responses((status = NO_CONTENT))
+)]
+pub async fn delete_one(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(hub): Extension<Arc<EventHub>>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path((submission_id, upload_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<String>)> {
+    // Aborts the S3 multipart upload (if still in progress) and removes the
+    // object, whether or not it was completed
+    check_submission_access(&db, &token, submission_id).await?;
+    let object = find_submission_upload(&db, submission_id, upload_id).await?;
+
+    match services::delete_object(&db, &s3, object.id).await {
+        Ok(_) => {
+            hub.publish(SubmissionEvent::Upload(UploadEvent::from_upload(
+                submission_id,
+                &object,
+                UploadState::Terminated,
+            )));
+            Ok(StatusCode::NO_CONTENT)
+        }
+        _ => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to delete upload".to_string()),
+        )),
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "partial_uploads")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub final_object_id: Option<Uuid>,
+    pub created_on: NaiveDateTime,
+    pub size_bytes: i64,
+    pub size_is_deferred: bool,
+    pub bytes_received: i64,
+    pub all_parts_received: bool,
+    pub last_part_received: Option<NaiveDateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "crate::submissions::db::Entity",
+        from = "Column::SubmissionId",
+        to = "crate::submissions::db::Column::Id"
+    )]
+    Submissions,
+    #[sea_orm(
+        belongs_to = "crate::uploads::db::Entity",
+        from = "Column::FinalObjectId",
+        to = "crate::uploads::db::Column::Id"
+    )]
+    FileObjects,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -2,10 +2,14 @@ use super::associations;
 use super::db;
 use crate::config::Config;
 use anyhow::Error;
+use aws_sdk_s3::operation::abort_multipart_upload::AbortMultipartUploadError;
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::Client as S3Client;
+use chrono::Utc;
 use sea_orm::entity::prelude::*;
-use sea_orm::{DatabaseConnection, EntityTrait};
+use sea_orm::{DatabaseConnection, EntityTrait, Set, SqlErr, TransactionTrait};
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub async fn delete_object(
@@ -13,35 +17,228 @@ pub async fn delete_object(
     s3: &Arc<S3Client>,
     id: Uuid,
 ) -> Result<(), Error> {
-    // Delete all associations for the object
-    associations::db::Entity::delete_many()
-        .filter(associations::db::Column::InputObjectId.eq(id.clone()))
-        .exec(db)
+    let obj = db::Entity::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Object not found"))?;
+
+    // Remove from S3 first, so a failure leaves the object tracked and the
+    // deletion can be retried rather than orphaning it in the bucket
+    let config = Config::from_env();
+    if let Some(multipart_upload_id) = &obj.multipart_upload_id {
+        // Abort the S3 multipart upload if it was never completed. S3 may
+        // have completed it without the row being updated, then there is
+        // nothing left to abort
+        match s3
+            .abort_multipart_upload()
+            .bucket(&config.s3_bucket)
+            .key(format!("{}/{}", config.s3_prefix, id))
+            .upload_id(multipart_upload_id)
+            .send()
+            .await
+        {
+            Ok(_) => {}
+            Err(err)
+                if matches!(
+                    err.as_service_error(),
+                    Some(AbortMultipartUploadError::NoSuchUpload(_))
+                ) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    s3.delete_object()
+        .bucket(&config.s3_bucket)
+        .key(format!("{}/{}", config.s3_prefix, id))
+        .send()
         .await
         .map_err(|e| Error::new(e))?;
 
-    // Find the object to delete
-    match db::Entity::find_by_id(id).one(db).await? {
-        Some(obj) => {
-            let obj: db::ActiveModel = obj.into();
-
-            // Delete from the database
-            let res = db::Entity::delete(obj).exec(db).await?;
-            if res.rows_affected == 0 {
-                return Err(anyhow::anyhow!("Object not found"));
-            }
-
-            // Delete from S3
-            let config = Config::from_env();
-            s3.delete_object()
-                .bucket(config.s3_bucket)
-                .key(format!("{}/{}", config.s3_prefix, id))
-                .send()
-                .await
-                .map_err(|e| Error::new(e))?;
-
-            Ok(())
+    // Then delete the associations and the object from the database
+    let txn = db.begin().await?;
+    associations::db::Entity::delete_many()
+        .filter(associations::db::Column::InputObjectId.eq(id))
+        .exec(&txn)
+        .await?;
+    let res = db::Entity::delete_by_id(id).exec(&txn).await?;
+    if res.rows_affected == 0 {
+        return Err(anyhow::anyhow!("Object not found"));
+    }
+    txn.commit().await?;
+
+    Ok(())
+}
+
+pub fn normalise_relative_path(
+    relative_path: Option<&str>,
+    filename: &str,
+) -> Result<String, Error> {
+    // Folder uploads provide the path of the file within the selected folder,
+    // ie. /pod5_pass/barcode01/reads.pod5. Files uploaded on their own do not,
+    // so fall back to the filename.
+    let relative_path = match relative_path {
+        Some(path) if !path.trim().is_empty() => path.trim(),
+        _ => filename,
+    };
+
+    let segments: Vec<&str> = relative_path
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("Relative path is empty"));
+    }
+    if segments.contains(&"..") {
+        return Err(anyhow::anyhow!(
+            "Relative path must not leave the upload folder"
+        ));
+    }
+    if segments.last() != Some(&filename) {
+        return Err(anyhow::anyhow!(
+            "Relative path does not end with the filename"
+        ));
+    }
+
+    Ok(segments.join("/"))
+}
+
+pub fn has_allowed_extension(filename: &str) -> bool {
+    let allowed_file_extensions: Vec<&str> = vec!["pod5"];
+    allowed_file_extensions.contains(&filename.split('.').next_back().unwrap())
+}
+
+pub async fn free_upload_path(
+    db: &DatabaseConnection,
+    s3: &Arc<S3Client>,
+    submission_id: Uuid,
+    relative_path: &str,
+) -> Result<bool, Error> {
+    // Returns false if the submission already has a complete file at that
+    // path. An incomplete upload at the path is deleted to allow a retry.
+    let results: Vec<(crate::submissions::db::Model, Vec<db::Model>)> =
+        crate::submissions::db::Entity::find()
+            .filter(crate::submissions::db::Column::Id.eq(submission_id))
+            .find_with_related(db::Entity)
+            .filter(db::Column::RelativePath.eq(relative_path))
+            .all(db)
+            .await?;
+
+    for existing_object in results.into_iter().flat_map(|(_, objs)| objs) {
+        if existing_object.all_parts_received {
+            return Ok(false);
         }
-        _ => Err(anyhow::anyhow!("Object not found")),
+        delete_object(db, s3, existing_object.id).await?;
+    }
+
+    Ok(true)
+}
+
+pub async fn create_object(
+    db: &DatabaseConnection,
+    submission_id: Uuid,
+    filename: String,
+    relative_path: String,
+    size_bytes: i64,
+) -> Result<db::Model, Error> {
+    // Create the file object and associate it to the submission, both or
+    // neither. The association is unique by path, see is_path_conflict.
+    let txn = db.begin().await?;
+    let object = db::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        created_on: Set(Utc::now().naive_utc()),
+        filename: Set(filename),
+        relative_path: Set(relative_path.clone()),
+        size_bytes: Set(size_bytes),
+        bytes_received: Set(0),
+        all_parts_received: Set(false),
+        last_part_received: Set(Some(Utc::now().naive_utc())),
+        processing_message: Set(Some("Upload initiated".to_string())),
+        multipart_upload_id: Set(None),
+    };
+    let object = db::Entity::insert(object).exec_with_returning(&txn).await?;
+
+    let association_object = associations::db::ActiveModel {
+        input_object_id: Set(object.id),
+        submission_id: Set(submission_id),
+        relative_path: Set(relative_path),
+        ..Default::default()
+    };
+    associations::db::Entity::insert(association_object)
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+
+    Ok(object)
+}
+
+pub fn is_path_conflict(err: &Error) -> bool {
+    // Another upload to the same path of the submission was created first
+    matches!(
+        err.downcast_ref::<DbErr>().and_then(|err| err.sql_err()),
+        Some(SqlErr::UniqueConstraintViolation(_))
+    )
+}
+
+pub async fn generate_download_url(s3: &S3Client, obj: &db::Model) -> Result<String, Error> {
+    // Presigned URL for the original file, named as it was uploaded
+    let config = Config::from_env();
+    let presigned_request = s3
+        .get_object()
+        .bucket(&config.s3_bucket)
+        .key(format!("{}/{}", config.s3_prefix, obj.id))
+        .response_content_disposition(format!(
+            "attachment; filename=\"{}\"",
+            obj.filename.replace('"', "")
+        ))
+        .presigned(
+            PresigningConfig::builder()
+                .expires_in(Duration::from_secs(15 * 60)) // Fifteen minutes
+                .build()?,
+        )
+        .await?;
+
+    Ok(presigned_request.uri().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_filename() {
+        assert_eq!(
+            normalise_relative_path(None, "reads.pod5").unwrap(),
+            "reads.pod5"
+        );
+        assert_eq!(
+            normalise_relative_path(Some("  "), "reads.pod5").unwrap(),
+            "reads.pod5"
+        );
+    }
+
+    #[test]
+    fn normalises_separators() {
+        assert_eq!(
+            normalise_relative_path(Some("/pod5_pass//barcode01/./reads.pod5"), "reads.pod5")
+                .unwrap(),
+            "pod5_pass/barcode01/reads.pod5"
+        );
+        assert_eq!(
+            normalise_relative_path(Some("pod5_pass\\barcode01\\reads.pod5"), "reads.pod5")
+                .unwrap(),
+            "pod5_pass/barcode01/reads.pod5"
+        );
+    }
+
+    #[test]
+    fn rejects_paths_leaving_the_folder() {
+        assert!(normalise_relative_path(Some("../reads.pod5"), "reads.pod5").is_err());
+        assert!(normalise_relative_path(Some("a/../../reads.pod5"), "reads.pod5").is_err());
+    }
+
+    #[test]
+    fn rejects_paths_not_ending_with_filename() {
+        assert!(normalise_relative_path(Some("a/other.pod5"), "reads.pod5").is_err());
+        assert!(normalise_relative_path(Some("/"), "reads.pod5").is_err());
     }
 }
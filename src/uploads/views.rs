@@ -8,12 +8,13 @@ use axum::{
     extract::{DefaultBodyLimit, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing, Json, Router,
+    routing, Extension, Json, Router,
 };
 use axum_keycloak_auth::{
-    instance::KeycloakAuthInstance, layer::KeycloakAuthLayer, PassthroughMode,
+    decode::KeycloakToken, instance::KeycloakAuthInstance, layer::KeycloakAuthLayer,
+    PassthroughMode,
 };
-use sea_orm::{query::*, DatabaseConnection, EntityTrait};
+use sea_orm::{query::*, DatabaseConnection, EntityTrait, ModelTrait};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -24,6 +25,20 @@ pub fn router(
     keycloak_auth_instance: Arc<KeycloakAuthInstance>,
     s3: Arc<S3Client>,
 ) -> Router {
+    // Downloads are checked against the submission owner in the handler, so
+    // any authenticated user may call them
+    let download_router = Router::new()
+        .route("/:id/download", routing::get(download_one))
+        .with_state((db.clone(), s3.clone()))
+        .layer(
+            KeycloakAuthLayer::<Role>::builder()
+                .instance(keycloak_auth_instance.clone())
+                .passthrough_mode(PassthroughMode::Block)
+                .persist_raw_claims(false)
+                .expected_audiences(vec![String::from("account")])
+                .build(),
+        );
+
     Router::new()
         .route("/", routing::get(get_all))
         .route("/:id", routing::get(get_one).delete(delete_one))
@@ -38,6 +53,7 @@ pub fn router(
                 .required_roles(vec![Role::Administrator])
                 .build(),
         )
+        .merge(download_router)
 }
 
 #[utoipa::path(
@@ -53,7 +69,10 @@ pub async fn get_all(
 
     let condition = apply_filters(
         params.filter.clone(),
-        &[("name", super::db::Column::Filename)],
+        &[
+            ("name", super::db::Column::Filename),
+            ("relative_path", super::db::Column::RelativePath),
+        ],
     );
 
     let (order_column, order_direction) = generic_sort(
@@ -62,7 +81,9 @@ pub async fn get_all(
             ("id", super::db::Column::Id),
             ("created_on", super::db::Column::CreatedOn),
             ("filename", super::db::Column::Filename),
+            ("relative_path", super::db::Column::RelativePath),
             ("size_bytes", super::db::Column::SizeBytes),
+            ("bytes_received", super::db::Column::BytesReceived),
             ("all_parts_received", super::db::Column::AllPartsReceived),
             ("last_part_received", super::db::Column::LastPartReceived),
             ("processing_message", super::db::Column::ProcessingMessage),
@@ -112,6 +133,56 @@ pub async fn get_one(
     Ok(Json(obj.into()))
 }
 
+#[utoipa::path(
+    get,
+    path = format!("/api/{}/{{id}}/download", RESOURCE_NAME),
+    responses((status = OK, body = crate::submissions::models::DownloadPath))
+)]
+pub async fn download_one(
+    State((db, s3)): State<(DatabaseConnection, Arc<S3Client>)>,
+    Extension(token): Extension<KeycloakToken<Role>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<crate::submissions::models::DownloadPath>, (StatusCode, Json<String>)> {
+    let obj = super::db::Entity::find_by_id(id)
+        .one(&db)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, Json("Not found".to_string())))?
+        .ok_or((StatusCode::NOT_FOUND, Json("Not found".to_string())))?;
+
+    // Only users who can see one of the object's submissions may download it
+    let submissions: Vec<crate::submissions::db::Model> = obj
+        .find_related(crate::submissions::db::Entity)
+        .all(&db)
+        .await
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json("Server error".to_string()),
+            )
+        })?;
+    if !submissions
+        .iter()
+        .any(|submission| crate::submissions::services::user_can_access(&token, submission))
+    {
+        return Err((StatusCode::NOT_FOUND, Json("Not found".to_string())));
+    }
+
+    if !obj.all_parts_received {
+        return Err((
+            StatusCode::CONFLICT,
+            Json("Upload is not complete".to_string()),
+        ));
+    }
+
+    match super::services::generate_download_url(&s3, &obj).await {
+        Ok(url) => Ok(Json(crate::submissions::models::DownloadPath { url })),
+        Err(_) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json("Failed to generate download URL".to_string()),
+        )),
+    }
+}
+
 #[utoipa::path(
     delete,
     path = format!("/api/{}/{{id}}", RESOURCE_NAME),